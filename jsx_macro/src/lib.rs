@@ -1,30 +1,281 @@
 #![recursion_limit = "128"]
 
-use proc_macro_hack::proc_macro_hack;
-
-
 extern crate proc_macro;
 
-use proc_macro2::{Literal, TokenStream, TokenTree};
-use quote::quote;
-use snax::{SnaxAttribute, SnaxItem, SnaxTag};
+use proc_macro2::{Delimiter, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+use snax::{SnaxAttribute, SnaxFragment, SnaxItem, SnaxSelfClosingTag, SnaxTag};
 
-#[proc_macro_hack]
+/// HTML elements that can't have children, so they never need a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+#[proc_macro]
 pub fn html(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let input = TokenStream::from(input);
+    let input = close_void_elements(TokenStream::from(input));
 
-    let parsed_content = snax::parse(input).expect("Could not even");
-    let output = create_node(&parsed_content);
-    // eprint!("{:#?}", parsed_content);
+    let output = match parse(input) {
+        Ok(parsed_content) => create_node(&parsed_content),
+        Err(error) => error.to_compile_error(),
+    };
     proc_macro::TokenStream::from(output)
 }
 
+/// A mistake in the markup given to `html!`, reported as a `compile_error!`
+/// pointing at the offending token
+struct HtmlError {
+    span: Span,
+    message: String,
+}
+
+impl HtmlError {
+    fn new(span: Span, message: String) -> HtmlError {
+        HtmlError { span, message }
+    }
+
+    fn to_compile_error(&self) -> TokenStream {
+        let message = &self.message;
+        quote_spanned!(self.span=> compile_error!(#message))
+    }
+}
+
+/// Checks the markup for the common mistakes first, so they get a precise
+/// error, then hands it over to snax
+fn parse(input: TokenStream) -> Result<SnaxItem, HtmlError> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    validate(&tokens)?;
+
+    let input: TokenStream = tokens.into_iter().collect();
+    snax::parse(input).map_err(|error| {
+        HtmlError::new(
+            Span::call_site(),
+            format!("html! could not parse this markup: {:?}", error),
+        )
+    })
+}
+
+fn is_punct(token: Option<&TokenTree>, c: char) -> bool {
+    match token {
+        Some(TokenTree::Punct(punct)) => punct.as_char() == c,
+        _ => false,
+    }
+}
+
+fn is_string_literal(literal: &Literal) -> bool {
+    let literal = literal.to_string();
+    literal.starts_with('"') || literal.starts_with("r\"") || literal.starts_with("r#")
+}
+
+/// Walks the tokens keeping a stack of open tags, a fragment is an open tag
+/// without a name
+fn validate(tokens: &[TokenTree]) -> Result<(), HtmlError> {
+    let mut open_tags: Vec<(String, Span)> = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let token = &tokens[i];
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '<' => {
+                i += 1;
+                match tokens.get(i) {
+                    // `<>` opens a fragment
+                    Some(TokenTree::Punct(next)) if next.as_char() == '>' => {
+                        open_tags.push((String::new(), punct.span()));
+                        i += 1;
+                    }
+                    // `</name>` or `</>` closes the innermost open tag
+                    Some(TokenTree::Punct(next)) if next.as_char() == '/' => {
+                        i += 1;
+                        let (name, span) = match tokens.get(i) {
+                            Some(TokenTree::Ident(name)) => {
+                                i += 1;
+                                (name.to_string(), name.span())
+                            }
+                            _ => (String::new(), next.span()),
+                        };
+                        if !is_punct(tokens.get(i), '>') {
+                            return Err(HtmlError::new(
+                                tokens.get(i).map_or(span, TokenTree::span),
+                                format!("expected `>` to end the closing tag `</{}`", name),
+                            ));
+                        }
+                        i += 1;
+
+                        match open_tags.pop() {
+                            Some((open_name, _)) if open_name == name => {}
+                            Some((open_name, _)) => {
+                                return Err(HtmlError::new(
+                                    span,
+                                    format!(
+                                        "mismatched closing tag, expected `</{}>` but found `</{}>`",
+                                        open_name, name
+                                    ),
+                                ));
+                            }
+                            None => {
+                                return Err(HtmlError::new(
+                                    span,
+                                    format!("unexpected closing tag `</{}>`, no tag is open", name),
+                                ));
+                            }
+                        }
+                    }
+                    Some(TokenTree::Ident(name)) => {
+                        i += 1;
+                        i = validate_attributes(tokens, i, &name.to_string())?;
+                        if is_punct(tokens.get(i), '/') {
+                            // self-closing, `/>`
+                            i += 2;
+                        } else {
+                            open_tags.push((name.to_string(), name.span()));
+                            i += 1;
+                        }
+                    }
+                    other => {
+                        return Err(HtmlError::new(
+                            other.map_or(punct.span(), TokenTree::span),
+                            String::from("expected a tag name, `>` or `/` after `<`"),
+                        ));
+                    }
+                }
+            }
+            TokenTree::Literal(literal) if is_string_literal(literal) => i += 1,
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => i += 1,
+            other => {
+                return Err(HtmlError::new(
+                    other.span(),
+                    format!(
+                        "unknown node `{}`, expected an element, a string literal or a `{{expression}}`",
+                        other
+                    ),
+                ));
+            }
+        }
+    }
+
+    match open_tags.pop() {
+        Some((name, span)) => Err(HtmlError::new(
+            span,
+            format!("`<{}>` is never closed, expected `</{}>`", name, name),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Checks the `name="value"` and `name={expr}` attributes of a tag starting at
+/// `i`, returns the index of the `>` or `/>` ending the tag
+fn validate_attributes(tokens: &[TokenTree], mut i: usize, tag: &str) -> Result<usize, HtmlError> {
+    loop {
+        match tokens.get(i) {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '>' => return Ok(i),
+            Some(TokenTree::Punct(punct))
+                if punct.as_char() == '/' && is_punct(tokens.get(i + 1), '>') =>
+            {
+                return Ok(i)
+            }
+            Some(TokenTree::Ident(name)) => {
+                if !is_punct(tokens.get(i + 1), '=') {
+                    return Err(HtmlError::new(
+                        tokens.get(i + 1).map_or(name.span(), TokenTree::span),
+                        format!(
+                            "unsupported attribute syntax, expected `{}=\"value\"` or `{}={{expression}}`",
+                            name, name
+                        ),
+                    ));
+                }
+                match tokens.get(i + 2) {
+                    // `width=42` would only fail later, as a type error in the expansion
+                    Some(TokenTree::Literal(literal)) if is_string_literal(literal) => {}
+                    Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {}
+                    other => {
+                        return Err(HtmlError::new(
+                            other.map_or(name.span(), TokenTree::span),
+                            format!(
+                                "unsupported value for attribute `{}`, attribute values must be string literals or `{{expression}}`",
+                                name
+                            ),
+                        ));
+                    }
+                }
+                i += 3;
+            }
+            Some(other) => {
+                return Err(HtmlError::new(
+                    other.span(),
+                    format!(
+                        "unsupported attribute syntax in `<{}>`, expected `name=\"value\"` or `name={{expression}}`",
+                        tag
+                    ),
+                ));
+            }
+            None => {
+                return Err(HtmlError::new(
+                    Span::call_site(),
+                    format!("`<{}` is never finished, expected `>`", tag),
+                ));
+            }
+        }
+    }
+}
+
+/// snax only understands void elements written as `<br/>`, so a `<br>` gets its
+/// missing slash before parsing, unless it is closed right away like `<br></br>`
+fn close_void_elements(input: TokenStream) -> TokenStream {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let mut output: Vec<TokenTree> = Vec::with_capacity(tokens.len());
+    let mut void_tag: Option<String> = None;
+
+    for (i, token) in tokens.iter().enumerate() {
+        if let TokenTree::Punct(punct) = token {
+            match punct.as_char() {
+                '<' => {
+                    void_tag = match tokens.get(i + 1) {
+                        Some(TokenTree::Ident(name))
+                            if VOID_ELEMENTS.contains(&name.to_string().as_str()) =>
+                        {
+                            Some(name.to_string())
+                        }
+                        _ => None,
+                    };
+                }
+                '>' if void_tag.is_some() => {
+                    let name = void_tag.take().unwrap();
+                    let closed = match output.last() {
+                        Some(TokenTree::Punct(previous)) => previous.as_char() == '/',
+                        _ => false,
+                    };
+                    if !closed && !closes_tag(&tokens[i + 1..], &name) {
+                        output.push(Punct::new('/', Spacing::Joint).into());
+                    }
+                }
+                _ => {}
+            }
+        }
+        output.push(token.clone());
+    }
+
+    output.into_iter().collect()
+}
+
+/// Whether `tokens` start with the closing tag `</name>`
+fn closes_tag(tokens: &[TokenTree], name: &str) -> bool {
+    is_punct(tokens.first(), '<')
+        && is_punct(tokens.get(1), '/')
+        && match tokens.get(2) {
+            Some(TokenTree::Ident(ident)) => ident == name,
+            _ => false,
+        }
+        && is_punct(tokens.get(3), '>')
+}
+
 fn create_node(item: &SnaxItem) -> TokenStream {
     match item {
         SnaxItem::Tag(tag) => create_tag(tag),
-        SnaxItem::SelfClosingTag(_tag) => TokenStream::new(),
+        SnaxItem::SelfClosingTag(tag) => create_self_closing_tag(tag),
         SnaxItem::Content(tt) => create_content(tt),
-        SnaxItem::Fragment(_fragment) => TokenStream::new(),
+        SnaxItem::Fragment(fragment) => create_fragment(fragment),
     }
 }
 
@@ -34,24 +285,93 @@ fn create_tag(tag: &SnaxTag) -> TokenStream {
 
     let tag_name_literal = Literal::string(&tag.name.to_string());
 
+    let node = quote!(
+        ::humus::render::h(#tag_name_literal, vec![#child_insertions], vec![#attribute_insertions])
+    );
+    add_listeners(&tag.attributes, node)
+}
+
+fn create_self_closing_tag(tag: &SnaxSelfClosingTag) -> TokenStream {
+    let attribute_insertions = emit_attributes(&tag.attributes);
+
+    let tag_name_literal = Literal::string(&tag.name.to_string());
+
+    let node = quote!(
+        ::humus::render::h(#tag_name_literal, vec![], vec![#attribute_insertions])
+    );
+    add_listeners(&tag.attributes, node)
+}
+
+/// `onclick={handler}` is an event listener rather than an attribute
+fn is_listener(name: &Ident, value: &TokenTree) -> bool {
+    let is_expression = match value {
+        TokenTree::Group(group) => group.delimiter() == Delimiter::Brace,
+        _ => false,
+    };
+    is_expression && name.to_string().starts_with("on")
+}
+
+/// Wraps an element node so it gets the listeners among `attributes`
+fn add_listeners(attributes: &[SnaxAttribute], node: TokenStream) -> TokenStream {
+    let listener_insertions: TokenStream = attributes
+        .iter()
+        .map(|attribute| match attribute {
+            SnaxAttribute::Simple { name, value } if is_listener(name, value) => {
+                let event_literal = Literal::string(&name.to_string()["on".len()..]);
+
+                quote!(
+                    ::humus::render::listener(#event_literal, #value),
+                )
+            }
+            _ => TokenStream::new(),
+        })
+        .collect();
+
+    if listener_insertions.is_empty() {
+        quote!({ #node })
+    } else {
+        quote!({
+            ::humus::render::with_listeners(vec![#listener_insertions], #node)
+        })
+    }
+}
+
+fn create_fragment(fragment: &SnaxFragment) -> TokenStream {
+    let child_insertions = emit_children(&fragment.children);
+
     quote!({
-        ::humus::render::h(#tag_name_literal, #child_insertions, #attribute_insertions)
+        ::humus::render::fragment(vec![#child_insertions])
     })
 }
 
-
+/// Emits every attribute but the listeners as an `attr` call followed by a comma, ready to go into a `vec![]`
 fn emit_attributes(attributes: &[SnaxAttribute]) -> TokenStream {
     attributes
         .iter()
         .map(|attribute| match attribute {
-            SnaxAttribute::Simple { name, value } => quote!(
-                ::humus::render::attr(#name, #value)
-            ),
+            SnaxAttribute::Simple { name, value } if is_listener(name, value) => TokenStream::new(),
+            SnaxAttribute::Simple { name, value } => {
+                let name_literal = Literal::string(&name.to_string());
+
+                match value {
+                    // `class={cls}`, anything that can be displayed becomes the attribute value
+                    TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                        let expression = group.stream();
+                        quote!(
+                            ::humus::render::attr(#name_literal, &::std::string::ToString::to_string(&(#expression))),
+                        )
+                    }
+                    _ => quote!(
+                        ::humus::render::attr(#name_literal, #value),
+                    ),
+                }
+            }
         })
         .collect()
 }
 
 
+/// Emits every child node followed by a comma, ready to go into a `vec![]`
 fn emit_children(children: &[SnaxItem]) -> TokenStream {
     children
         .iter()
@@ -59,7 +379,7 @@ fn emit_children(children: &[SnaxItem]) -> TokenStream {
             let emitted = create_node(child);
 
             quote!(
-                #emitted
+                #emitted,
             )
         })
         .collect()
@@ -67,7 +387,17 @@ fn emit_children(children: &[SnaxItem]) -> TokenStream {
 
 
 fn create_content(tt: &TokenTree) -> TokenStream {
-    quote!(
-        ::humus::render::t(#tt);
-    )
+    match tt {
+        // `{expr}` children can be strings, nodes, options or iterators of nodes
+        TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+            let expression = group.stream();
+            quote!({
+                use ::humus::render::IntoChildNode as _;
+                ::humus::render::Child(#expression).into_node()
+            })
+        }
+        _ => quote!(
+            ::humus::render::t(#tt)
+        ),
+    }
 }
\ No newline at end of file
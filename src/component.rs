@@ -0,0 +1,255 @@
+use super::{
+    node::{Element, VirtualDomNode},
+    scheduler::Scheduler,
+    vdom::VirtualDom,
+};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+/// A piece of UI that owns its state and renders it as a virtual DOM tree.
+///
+/// ```ignore
+/// struct Counter {
+///     count: u32,
+///     link: Link<Counter>,
+/// }
+///
+/// impl Component for Counter {
+///     fn render(&self) -> VirtualDomNode {
+///         let onclick = self.link.callback(|counter, _| counter.count += 1);
+///         html! { <button onclick={onclick}>{self.count.to_string()}</button> }
+///     }
+/// }
+///
+/// let app = App::mount(root, |link| Counter { count: 0, link });
+/// ```
+pub trait Component: 'static {
+    /// Renders the current state, this must not update the state itself
+    fn render(&self) -> VirtualDomNode;
+
+    /// Called once the element the component rendered is in the DOM, after the
+    /// `mounted` hooks of the components below it
+    fn mounted(&mut self, _element: Element) {}
+
+    /// Called after every render that kept the element in the DOM, once the
+    /// whole tree is patched
+    fn updated(&mut self, _element: Element) {}
+
+    /// Called right before the element is removed from the DOM, before the
+    /// `before_unmount` hooks of the components below it
+    fn before_unmount(&mut self, _element: Element) {}
+}
+
+/// The lifecycle hooks an element calls for the component that rendered it.
+///
+/// Hooks run while the DOM is being patched, so to change the state from a
+/// hook, change it in place and ask the `Link` to `schedule_render`.
+pub trait Lifecycle {
+    fn mounted(&self, element: Element);
+    fn updated(&self, element: Element);
+    fn before_unmount(&self, element: Element);
+}
+
+impl<C: Component> Lifecycle for RefCell<C> {
+    fn mounted(&self, element: Element) {
+        self.borrow_mut().mounted(element);
+    }
+
+    fn updated(&self, element: Element) {
+        self.borrow_mut().updated(element);
+    }
+
+    fn before_unmount(&self, element: Element) {
+        self.borrow_mut().before_unmount(element);
+    }
+}
+
+/// Renders a component owned by another component, the element it renders gets
+/// the component's lifecycle hooks
+pub fn embed<C: Component>(component: &Rc<RefCell<C>>) -> VirtualDomNode {
+    let node = component.borrow().render();
+    with_hooks(component.clone(), node)
+}
+
+fn with_hooks(hooks: Rc<dyn Lifecycle>, node: VirtualDomNode) -> VirtualDomNode {
+    match node {
+        VirtualDomNode::ElementNode(mut vnode) => {
+            vnode.hooks = Some(hooks);
+            VirtualDomNode::ElementNode(vnode)
+        }
+        // there is no single element to hand to the hooks
+        node => node,
+    }
+}
+
+/// A component mounted on a root element, re-rendered whenever its state is
+/// updated. Renders are batched, at most one runs per animation frame.
+pub struct App<C: Component> {
+    state: Rc<AppState<C>>,
+    scheduler: Scheduler,
+}
+
+struct AppState<C> {
+    component: RefCell<Option<C>>,
+    vdom: RefCell<VirtualDom>,
+    root: Element,
+    // the same hooks on every render, so the root element is diffed as the same component
+    hooks: RefCell<Option<Rc<dyn Lifecycle>>>,
+}
+
+impl<C: Component> App<C> {
+    /// Creates the component with a `Link` back to the app and renders it into `root`
+    pub fn mount<F>(root: Element, create: F) -> App<C>
+    where
+        F: FnOnce(Link<C>) -> C,
+    {
+        let state = Rc::new(AppState {
+            component: RefCell::new(None),
+            vdom: RefCell::new(VirtualDom::new()),
+            root,
+            hooks: RefCell::new(None),
+        });
+        let weak = Rc::downgrade(&state);
+        let scheduler = Scheduler::new(move || {
+            if let Some(state) = weak.upgrade() {
+                AppState::render(&state);
+            }
+        });
+        let app = App { state, scheduler };
+
+        let component = create(app.link());
+        *app.state.component.borrow_mut() = Some(component);
+
+        // the first render doesn't wait for a frame
+        app.scheduler.schedule_render();
+        app.flush();
+        app
+    }
+
+    /// A new link to this app, for handlers created outside of the component
+    pub fn link(&self) -> Link<C> {
+        Link {
+            state: Rc::downgrade(&self.state),
+            scheduler: self.scheduler.clone(),
+        }
+    }
+
+    /// Runs `f` with a shared reference to the component
+    pub fn with<R, F: FnOnce(&C) -> R>(&self, f: F) -> R {
+        f(self.state.component.borrow().as_ref().unwrap())
+    }
+
+    /// Updates the component's state and schedules a render
+    pub fn update<F: FnOnce(&mut C)>(&self, f: F) {
+        self.link().update(f);
+    }
+
+    /// Runs a scheduled render right away, so tests can check the DOM without
+    /// waiting for an animation frame
+    pub fn flush(&self) {
+        self.scheduler.flush();
+    }
+
+    /// Removes the rendered tree from `root`, calling the `before_unmount` hooks
+    pub fn unmount(self) {
+        // a render scheduled now would bring the tree back
+        self.flush();
+        let mut root = self.state.root.clone();
+        self.state.vdom.borrow_mut().unmount(&mut root);
+    }
+}
+
+impl<C: Component> AppState<C> {
+    /// Renders the component and patches the DOM. The scheduler makes sure this
+    /// never runs while it is already running, an update made while patching
+    /// the DOM, say from a `blur` handler, is rendered right after.
+    fn render(state: &Rc<AppState<C>>) {
+        let node = match state.component.borrow().as_ref() {
+            Some(component) => component.render(),
+            None => return,
+        };
+        let hooks = state
+            .hooks
+            .borrow_mut()
+            .get_or_insert_with(|| Rc::new(RootHooks(Rc::downgrade(state))))
+            .clone();
+        let node = with_hooks(hooks, node);
+        let mut root = state.root.clone();
+        state.vdom.borrow_mut().render(&mut root, node);
+    }
+}
+
+/// Calls the root component's hooks, weakly so the tree doesn't keep the app alive
+struct RootHooks<C>(Weak<AppState<C>>);
+
+impl<C: Component> RootHooks<C> {
+    fn with_component<F: FnOnce(&mut C)>(&self, f: F) {
+        if let Some(state) = self.0.upgrade() {
+            if let Some(component) = state.component.borrow_mut().as_mut() {
+                f(component);
+            }
+        }
+    }
+}
+
+impl<C: Component> Lifecycle for RootHooks<C> {
+    fn mounted(&self, element: Element) {
+        self.with_component(|component| component.mounted(element));
+    }
+
+    fn updated(&self, element: Element) {
+        self.with_component(|component| component.updated(element));
+    }
+
+    fn before_unmount(&self, element: Element) {
+        self.with_component(|component| component.before_unmount(element));
+    }
+}
+
+/// A weak handle to a mounted `App`, so event handlers can update the state.
+/// Once the app is dropped, updates through the link do nothing.
+pub struct Link<C> {
+    state: Weak<AppState<C>>,
+    scheduler: Scheduler,
+}
+
+impl<C> Clone for Link<C> {
+    fn clone(&self) -> Link<C> {
+        Link {
+            state: self.state.clone(),
+            scheduler: self.scheduler.clone(),
+        }
+    }
+}
+
+impl<C: Component> Link<C> {
+    /// Updates the component's state and schedules a render, however many
+    /// updates happen before the next frame there is only one render.
+    /// Must not be called from `Component::render`.
+    pub fn update<F: FnOnce(&mut C)>(&self, f: F) {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        if let Some(component) = state.component.borrow_mut().as_mut() {
+            f(component);
+        }
+        self.scheduler.schedule_render();
+    }
+
+    /// Renders the component again on the next frame without changing its state
+    pub fn schedule_render(&self) {
+        if self.state.upgrade().is_some() {
+            self.scheduler.schedule_render();
+        }
+    }
+
+    /// An event listener callback that updates the component's state
+    pub fn callback<F>(&self, f: F) -> impl Fn(web_sys::Event)
+    where
+        F: 'static + Fn(&mut C, web_sys::Event),
+    {
+        let link = self.clone();
+        move |event| link.update(|component| f(component, event))
+    }
+}
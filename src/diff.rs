@@ -0,0 +1,539 @@
+use super::component::Lifecycle;
+use super::node::{Attribute, Listener, NodeKey, VirtualDomNode};
+use longest_increasing_subsequence::lis;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Child indices leading from the mount root down to a real DOM node.
+///
+/// `vec![0, 2]` is the third child of the first child of the root element.
+pub type Path = Vec<usize>;
+
+/// A single change that has to be made to the real DOM so that it matches
+/// the new virtual dom tree.
+///
+/// Patches are meant to be applied in the order they were emitted, every
+/// path is valid for the state of the DOM right after the previous patch.
+#[derive(Debug)]
+pub enum Patch<'a> {
+    /// Create the real DOM for `node` and insert it as child number `index`
+    /// of the node at `parent`
+    Create {
+        parent: Path,
+        index: usize,
+        node: &'a VirtualDomNode,
+    },
+    /// Move child number `from` of the node at `parent` so it ends up as
+    /// child number `to`, `to` is counted without the moved child
+    Move {
+        parent: Path,
+        from: usize,
+        to: usize,
+    },
+    /// Remove the real DOM of `node`, the node at `path`, from the DOM
+    Remove {
+        path: Path,
+        node: &'a VirtualDomNode,
+    },
+    /// Create the real DOM for `node` and put it in place of `old`, the node at `path`
+    Replace {
+        path: Path,
+        old: &'a VirtualDomNode,
+        node: &'a VirtualDomNode,
+    },
+    /// Change the content of the text node at `path`
+    SetText { path: Path, text: &'a str },
+    /// Create the real DOM for `node` and append it to the node at `parent`
+    AppendChild {
+        parent: Path,
+        node: &'a VirtualDomNode,
+    },
+    /// Replace everything inside the element at `path` with `html`
+    SetInnerHtml { path: Path, html: &'a str },
+    /// Add or change an attribute of the element at `path`
+    SetAttribute {
+        path: Path,
+        name: &'a str,
+        value: &'a str,
+    },
+    /// Remove an attribute from the element at `path`
+    RemoveAttribute { path: Path, name: &'a str },
+    /// Attach `listener` to the element at `path`
+    AddListener {
+        path: Path,
+        listener: &'a Listener,
+    },
+    /// Let the element at `path` call `new` instead of `old`, this doesn't
+    /// touch the DOM
+    UpdateListener {
+        path: Path,
+        old: &'a Listener,
+        new: &'a Listener,
+    },
+    /// Detach `listener` from the element at `path`
+    RemoveListener {
+        path: Path,
+        listener: &'a Listener,
+    },
+    /// Call the `updated` hook of `node`, the element at `path`, once the DOM is
+    /// patched. This doesn't touch the DOM.
+    Updated {
+        path: Path,
+        node: &'a VirtualDomNode,
+    },
+}
+
+/// Compares two virtual dom trees that live at `child_index` of the mount root
+/// and returns the patches needed to turn the old one into the new one.
+///
+/// Two identical trees produce no patches that touch the DOM. Volatile
+/// attributes are always set again and listeners always get their new callback.
+pub fn diff<'a>(
+    child_index: usize,
+    new_node: &'a VirtualDomNode,
+    old_node: &'a VirtualDomNode,
+) -> Vec<Patch<'a>> {
+    let mut patches = Vec::new();
+    diff_node(&mut patches, &Path::new(), child_index, new_node, old_node);
+    patches
+}
+
+fn child_path(parent: &Path, index: usize) -> Path {
+    let mut path = parent.clone();
+    path.push(index);
+    path
+}
+
+fn diff_node<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    parent: &Path,
+    child_index: usize,
+    new_node: &'a VirtualDomNode,
+    old_node: &'a VirtualDomNode,
+) {
+    let path = child_path(parent, child_index);
+    match (old_node, new_node) {
+        (VirtualDomNode::Empty, VirtualDomNode::Empty) => {}
+        (VirtualDomNode::TextNode(old_text), VirtualDomNode::TextNode(new_text)) => {
+            if old_text.text != new_text.text {
+                patches.push(Patch::SetText {
+                    path,
+                    text: &new_text.text,
+                });
+            }
+        }
+        (VirtualDomNode::ElementNode(old_vnode), VirtualDomNode::ElementNode(new_vnode))
+            if old_vnode.node_type == new_vnode.node_type
+                && same_hooks(&old_vnode.hooks, &new_vnode.hooks) =>
+        {
+            diff_attributes(patches, &path, &new_vnode.attributes, &old_vnode.attributes);
+            diff_listeners(patches, &path, &new_vnode.listeners, &old_vnode.listeners);
+            diff_children(
+                patches,
+                &path,
+                0,
+                &new_vnode.children,
+                &old_vnode.children,
+                true,
+            );
+            // after the children, so their hooks run before ours
+            if new_vnode.hooks.is_some() {
+                patches.push(Patch::Updated {
+                    path,
+                    node: new_node,
+                });
+            }
+        }
+        (VirtualDomNode::RawHtml(old_raw), VirtualDomNode::RawHtml(new_raw))
+            if old_raw.node_type == new_raw.node_type =>
+        {
+            if old_raw.html != new_raw.html {
+                patches.push(Patch::SetInnerHtml {
+                    path,
+                    html: &new_raw.html,
+                });
+            }
+        }
+        // fragment children are flattened into the parent, starting at our index
+        (VirtualDomNode::Fragment(old_children), VirtualDomNode::Fragment(new_children)) => {
+            diff_children(
+                patches,
+                parent,
+                child_index,
+                new_children,
+                old_children,
+                false,
+            );
+        }
+        // Different elements or nodes of different kinds, an empty placeholder is swapped the same way
+        _ => replace_node(patches, parent, child_index, new_node, old_node),
+    }
+}
+
+/// Whether both elements were rendered by the same component, or by none. An
+/// element rendered by another component is replaced, so the old component
+/// gets `before_unmount` and the new one `mounted` instead of `updated`.
+fn same_hooks(old: &Option<Rc<dyn Lifecycle>>, new: &Option<Rc<dyn Lifecycle>>) -> bool {
+    match (old, new) {
+        (None, None) => true,
+        // only the data pointers, the same component may come with another vtable
+        (Some(old), Some(new)) => Rc::as_ptr(old) as *const u8 == Rc::as_ptr(new) as *const u8,
+        _ => false,
+    }
+}
+
+/// Puts `new_node` in place of the real DOM of `old_node`
+fn replace_node<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    parent: &Path,
+    child_index: usize,
+    new_node: &'a VirtualDomNode,
+    old_node: &'a VirtualDomNode,
+) {
+    match (old_node, new_node) {
+        (VirtualDomNode::Fragment(_), _) | (_, VirtualDomNode::Fragment(_)) => {
+            remove_node(patches, parent, child_index, old_node);
+            create_node(patches, parent, Some(child_index), new_node);
+        }
+        _ => patches.push(Patch::Replace {
+            path: child_path(parent, child_index),
+            old: old_node,
+            node: new_node,
+        }),
+    }
+}
+
+/// Creates `node` at `index` of `parent`, or at its end when there is no index.
+/// Fragments are flattened, so no patch ever carries a fragment.
+fn create_node<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    parent: &Path,
+    index: Option<usize>,
+    node: &'a VirtualDomNode,
+) {
+    match (node, index) {
+        (VirtualDomNode::Fragment(children), _) => {
+            let mut index = index;
+            for child in children.iter() {
+                create_node(patches, parent, index, child);
+                index = index.map(|i| i + child.dom_len());
+            }
+        }
+        (_, Some(index)) => patches.push(Patch::Create {
+            parent: parent.clone(),
+            index,
+            node,
+        }),
+        (_, None) => patches.push(Patch::AppendChild {
+            parent: parent.clone(),
+            node,
+        }),
+    }
+}
+
+/// Removes every real DOM node of `node`, which starts at `index` of `parent`.
+/// Fragments are flattened, so no patch ever carries a fragment.
+fn remove_node<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    parent: &Path,
+    index: usize,
+    node: &'a VirtualDomNode,
+) {
+    match node {
+        VirtualDomNode::Fragment(children) => {
+            // starting from the back so the remaining indices stay valid
+            let mut end = index + node.dom_len();
+            for child in children.iter().rev() {
+                end -= child.dom_len();
+                remove_node(patches, parent, end, child);
+            }
+        }
+        _ => patches.push(Patch::Remove {
+            path: child_path(parent, index),
+            node,
+        }),
+    }
+}
+
+fn diff_attributes<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    path: &Path,
+    new_attributes: &'a [Attribute],
+    old_attributes: &'a [Attribute],
+) {
+    // attributes that must not be rendered are compared as if they weren't there
+    for new_attr in new_attributes.iter() {
+        let value = match new_attr.rendered_value() {
+            Some(value) => value,
+            // a volatile one like `checked="false"` may have been turned on by the user
+            None if new_attr.is_volatile() => {
+                patches.push(Patch::RemoveAttribute {
+                    path: path.clone(),
+                    name: &new_attr.name,
+                });
+                continue;
+            }
+            None => continue,
+        };
+        // volatile attributes may have been changed by the user, so they are always re-applied
+        let unchanged = !new_attr.is_volatile()
+            && old_attributes.iter().any(|old_attr| {
+                old_attr.name == new_attr.name && old_attr.rendered_value() == Some(value)
+            });
+        if !unchanged {
+            patches.push(Patch::SetAttribute {
+                path: path.clone(),
+                name: &new_attr.name,
+                value,
+            });
+        }
+    }
+
+    for old_attr in old_attributes.iter() {
+        let rendered = |attr: &Attribute| attr.rendered_value().is_some();
+        if rendered(old_attr)
+            && !new_attributes
+                .iter()
+                .any(|new_attr| {
+                    new_attr.name == old_attr.name && (rendered(new_attr) || new_attr.is_volatile())
+                })
+        {
+            patches.push(Patch::RemoveAttribute {
+                path: path.clone(),
+                name: &old_attr.name,
+            });
+        }
+    }
+}
+
+fn diff_listeners<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    path: &Path,
+    new_listeners: &'a [Listener],
+    old_listeners: &'a [Listener],
+) {
+    for new_listener in new_listeners.iter() {
+        let old_listener = old_listeners
+            .iter()
+            .find(|old_listener| old_listener.event == new_listener.event);
+        match old_listener {
+            Some(old) => patches.push(Patch::UpdateListener {
+                path: path.clone(),
+                old,
+                new: new_listener,
+            }),
+            None => patches.push(Patch::AddListener {
+                path: path.clone(),
+                listener: new_listener,
+            }),
+        }
+    }
+
+    for old_listener in old_listeners.iter() {
+        if !new_listeners
+            .iter()
+            .any(|new_listener| new_listener.event == old_listener.event)
+        {
+            patches.push(Patch::RemoveListener {
+                path: path.clone(),
+                listener: old_listener,
+            });
+        }
+    }
+}
+
+/// Diffs two lists of children whose real DOM starts at index `start` of `parent`.
+///
+/// `whole_parent` tells whether the lists are all children of `parent`, which
+/// is not the case for fragments, so new trailing children can be appended.
+fn diff_children<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    parent: &Path,
+    start: usize,
+    new_children: &'a [VirtualDomNode],
+    old_children: &'a [VirtualDomNode],
+    whole_parent: bool,
+) {
+    if all_keyed(new_children) && all_keyed(old_children) {
+        diff_keyed_children(patches, parent, start, new_children, old_children);
+    } else {
+        diff_unkeyed_children(
+            patches,
+            parent,
+            start,
+            new_children,
+            old_children,
+            whole_parent,
+        );
+    }
+}
+
+/// Whether every child is an element with a key of its own. Only then can the
+/// children be matched by key, a list mixing keyed and unkeyed children, with
+/// duplicate keys or with fragments is diffed by position instead.
+fn all_keyed(children: &[VirtualDomNode]) -> bool {
+    let mut keys = HashSet::new();
+    children.iter().all(|child| match child {
+        VirtualDomNode::ElementNode(vnode) => vnode.key.is_some() && keys.insert(vnode.key),
+        _ => false,
+    })
+}
+
+fn diff_unkeyed_children<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    parent: &Path,
+    start: usize,
+    new_children: &'a [VirtualDomNode],
+    old_children: &'a [VirtualDomNode],
+    whole_parent: bool,
+) {
+    let min_length = cmp::min(new_children.len(), old_children.len());
+    let mut index = start;
+
+    // loop through the children nodes of both the old and new element and recursively update them
+    for i in 0..min_length {
+        diff_node(patches, parent, index, &new_children[i], &old_children[i]);
+        index += new_children[i].dom_len();
+    }
+
+    // if we have more node children on the new element add them to the real DOM
+    for child in new_children[min_length..].iter() {
+        if whole_parent {
+            create_node(patches, parent, None, child);
+        } else {
+            create_node(patches, parent, Some(index), child);
+            index += child.dom_len();
+        }
+    }
+
+    // if we have less node children than the old node, remove excess real DOM children
+    // starting from the back so the remaining indices stay valid
+    let mut end = index
+        + old_children[min_length..]
+            .iter()
+            .map(|child| child.dom_len())
+            .sum::<usize>();
+    for child in old_children[min_length..].iter().rev() {
+        end -= child.dom_len();
+        remove_node(patches, parent, end, child);
+    }
+}
+
+/// Diffs children that all carry a unique `NodeKey`, matching old and new children
+/// by key instead of by position. Keyed children are elements, so each of them is
+/// exactly one real DOM node and child `i` is DOM child `start + i`.
+///
+/// Children whose old indices form the longest increasing subsequence are
+/// already in the right order relative to each other and are left in place,
+/// every other surviving child is moved, so reordering a list takes the fewest
+/// possible DOM moves.
+fn diff_keyed_children<'a>(
+    patches: &mut Vec<Patch<'a>>,
+    path: &Path,
+    start: usize,
+    new_children: &'a [VirtualDomNode],
+    old_children: &'a [VirtualDomNode],
+) {
+    debug_assert!(all_keyed(new_children) && all_keyed(old_children));
+    let old_length = old_children.len();
+    let new_length = new_children.len();
+
+    let new_index_by_key: HashMap<NodeKey, usize> = new_children
+        .iter()
+        .enumerate()
+        .map(|(i, child)| (child.key(), i))
+        .collect();
+    let old_index_by_key: HashMap<NodeKey, usize> = old_children
+        .iter()
+        .enumerate()
+        .map(|(i, child)| (child.key(), i))
+        .collect();
+
+    // The real DOM children in their current order. Old children are identified
+    // by their old index, newly created ones by `old_length + new index`.
+    let mut dom_children: Vec<usize> = (0..old_length).collect();
+
+    // remove the old children whose key is gone, from the back so the
+    // remaining indices stay valid
+    for i in (0..old_length).rev() {
+        if !new_index_by_key.contains_key(&old_children[i].key()) {
+            patches.push(Patch::Remove {
+                path: child_path(path, start + i),
+                node: &old_children[i],
+            });
+            dom_children.remove(i);
+        }
+    }
+
+    // old index of every new child that already has a real DOM node
+    let sources: Vec<Option<usize>> = new_children
+        .iter()
+        .map(|child| old_index_by_key.get(&child.key()).cloned())
+        .collect();
+    let reused: Vec<(usize, usize)> = sources
+        .iter()
+        .enumerate()
+        .filter_map(|(new_index, source)| source.map(|old_index| (new_index, old_index)))
+        .collect();
+    let old_indices: Vec<usize> = reused.iter().map(|(_, old_index)| *old_index).collect();
+
+    let mut stays = vec![false; new_length];
+    for i in lis(&old_indices) {
+        stays[reused[i].0] = true;
+    }
+
+    let dom_id = |new_index: usize| sources[new_index].unwrap_or(old_length + new_index);
+    let position = |dom_children: &Vec<usize>, id: usize| {
+        dom_children
+            .iter()
+            .position(|dom_id| *dom_id == id)
+            .expect("keyed child is missing from the DOM")
+    };
+
+    // walk backwards and put every child right in front of its next sibling,
+    // which is already in its final place
+    for new_index in (0..new_length).rev() {
+        let before = if new_index + 1 < new_length {
+            position(&dom_children, dom_id(new_index + 1))
+        } else {
+            dom_children.len()
+        };
+
+        match sources[new_index] {
+            None => {
+                patches.push(Patch::Create {
+                    parent: path.clone(),
+                    index: start + before,
+                    node: &new_children[new_index],
+                });
+                dom_children.insert(before, dom_id(new_index));
+            }
+            Some(old_index) if !stays[new_index] => {
+                let from = position(&dom_children, old_index);
+                let to = if from < before { before - 1 } else { before };
+                if from != to {
+                    patches.push(Patch::Move {
+                        parent: path.clone(),
+                        from: start + from,
+                        to: start + to,
+                    });
+                    dom_children.remove(from);
+                    dom_children.insert(to, old_index);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    // every child now sits at its new index, update the reused ones in place
+    for (new_index, old_index) in reused {
+        diff_node(
+            patches,
+            path,
+            start + new_index,
+            &new_children[new_index],
+            &old_children[old_index],
+        );
+    }
+}
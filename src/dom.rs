@@ -0,0 +1,209 @@
+use super::node::{is_volatile_attribute, Element, Listener};
+use wasm_bindgen::{JsCast, JsValue};
+
+/// The DOM operations rendering needs, so the same diff and patch code can
+/// drive the browser's DOM or the in-memory one from `memory`.
+pub trait DomBackend {
+    /// A handle to a node of this DOM, cloning it gives another handle to the same node
+    type Node: Clone;
+
+    fn create_element(&self, tag: &str) -> Self::Node;
+
+    fn create_text_node(&self, text: &str) -> Self::Node;
+
+    /// An empty comment, the placeholder rendered for `VirtualDomNode::Empty`
+    fn create_placeholder(&self) -> Self::Node;
+
+    /// Child number `index` of `parent`, counting every kind of node
+    fn child(&self, parent: &Self::Node, index: usize) -> Option<Self::Node>;
+
+    /// Puts `child` right before `reference` among the children of `parent`,
+    /// or at the end without a reference. A child that is already in the DOM is moved.
+    fn insert_before(
+        &self,
+        parent: &Self::Node,
+        child: &Self::Node,
+        reference: Option<&Self::Node>,
+    );
+
+    fn remove_child(&self, parent: &Self::Node, child: &Self::Node);
+
+    fn replace_child(&self, parent: &Self::Node, new_child: &Self::Node, old_child: &Self::Node);
+
+    /// Changes the content of a text node
+    fn set_text(&self, node: &Self::Node, text: &str);
+
+    fn set_attribute(&self, element: &Self::Node, name: &str, value: &str);
+
+    fn remove_attribute(&self, element: &Self::Node, name: &str);
+
+    /// Replaces the children of `element` with the nodes of `html`, which is
+    /// trusted and used as it is
+    fn set_inner_html(&self, element: &Self::Node, html: &str);
+
+    /// The tag of an element, lowercase for HTML elements but case-sensitive
+    /// for SVG ones like `foreignObject`. `None` for text and comments.
+    fn tag_name(&self, node: &Self::Node) -> Option<String>;
+
+    /// The content of a text node, `None` for elements and comments
+    fn text(&self, node: &Self::Node) -> Option<String>;
+
+    /// The names of the attributes `element` has, in the DOM's order
+    fn attribute_names(&self, element: &Self::Node) -> Vec<String>;
+
+    fn attribute(&self, element: &Self::Node, name: &str) -> Option<String>;
+
+    fn append_child(&self, parent: &Self::Node, child: &Self::Node) {
+        self.insert_before(parent, child, None);
+    }
+
+    /// Attaches `listener` to `element`, a DOM without events ignores it
+    fn add_listener(&self, _element: &Self::Node, _listener: &Listener) {}
+
+    /// The `Element` handed to lifecycle hooks, a DOM without real elements
+    /// returns `None` and the hooks don't run
+    fn element(&self, _node: &Self::Node) -> Option<Element> {
+        None
+    }
+}
+
+/// The browser's DOM, through `web_sys`
+pub struct WebDom {
+    document: web_sys::Document,
+}
+
+impl WebDom {
+    pub fn new() -> WebDom {
+        let window = web_sys::window().expect("no global `window` exists");
+        WebDom {
+            document: window.document().expect("Document"),
+        }
+    }
+}
+
+impl Default for WebDom {
+    fn default() -> WebDom {
+        WebDom::new()
+    }
+}
+
+impl DomBackend for WebDom {
+    type Node = web_sys::Node;
+
+    fn create_element(&self, tag: &str) -> web_sys::Node {
+        self.document.create_element(tag).unwrap().into()
+    }
+
+    fn create_text_node(&self, text: &str) -> web_sys::Node {
+        self.document.create_text_node(text).into()
+    }
+
+    fn create_placeholder(&self) -> web_sys::Node {
+        self.document.create_comment("").into()
+    }
+
+    fn child(&self, parent: &web_sys::Node, index: usize) -> Option<web_sys::Node> {
+        parent.child_nodes().item(index as u32)
+    }
+
+    fn insert_before(
+        &self,
+        parent: &web_sys::Node,
+        child: &web_sys::Node,
+        reference: Option<&web_sys::Node>,
+    ) {
+        parent.insert_before(child, reference).unwrap();
+    }
+
+    fn remove_child(&self, parent: &web_sys::Node, child: &web_sys::Node) {
+        parent.remove_child(child).unwrap();
+    }
+
+    fn replace_child(
+        &self,
+        parent: &web_sys::Node,
+        new_child: &web_sys::Node,
+        old_child: &web_sys::Node,
+    ) {
+        parent.replace_child(new_child, old_child).unwrap();
+    }
+
+    fn set_text(&self, node: &web_sys::Node, text: &str) {
+        node.set_node_value(Some(text));
+    }
+
+    /// Volatile attributes are written to the DOM property instead, since that
+    /// is what user input changes and what the browser displays. A boolean one
+    /// is only ever set to turn it on, a `"false"` one is removed instead.
+    fn set_attribute(&self, element: &web_sys::Node, name: &str, value: &str) {
+        let el = to_element(element);
+        if is_volatile_attribute(name) {
+            let value = match name {
+                "value" => JsValue::from_str(value),
+                _ => JsValue::from_bool(true),
+            };
+            let _res = el.set_property(name, &value);
+        } else {
+            let _res = el.set_attribute(name, value);
+        }
+    }
+
+    /// Volatile attributes get their DOM property reset as well
+    fn remove_attribute(&self, element: &web_sys::Node, name: &str) {
+        let el = to_element(element);
+        let _res = el.remove_attribute(name);
+        if is_volatile_attribute(name) {
+            let value = match name {
+                "value" => JsValue::from_str(""),
+                _ => JsValue::from_bool(false),
+            };
+            let _res = el.set_property(name, &value);
+        }
+    }
+
+    fn set_inner_html(&self, element: &web_sys::Node, html: &str) {
+        to_element(element).el.unwrap().set_inner_html(html);
+    }
+
+    fn tag_name(&self, node: &web_sys::Node) -> Option<String> {
+        // `tagName` is upper-cased for HTML elements, the local name is lowercase
+        // for them and keeps the case of SVG tags like `foreignObject`
+        let el = node.dyn_ref::<web_sys::Element>()?;
+        Some(el.local_name())
+    }
+
+    fn text(&self, node: &web_sys::Node) -> Option<String> {
+        if node.node_type() == web_sys::Node::TEXT_NODE {
+            node.node_value()
+        } else {
+            None
+        }
+    }
+
+    fn attribute_names(&self, element: &web_sys::Node) -> Vec<String> {
+        let attributes = match element.dyn_ref::<web_sys::Element>() {
+            Some(el) => el.attributes(),
+            None => return Vec::new(),
+        };
+        (0..attributes.length())
+            .filter_map(|i| attributes.item(i))
+            .map(|attribute| attribute.name())
+            .collect()
+    }
+
+    fn attribute(&self, element: &web_sys::Node, name: &str) -> Option<String> {
+        element.dyn_ref::<web_sys::Element>()?.get_attribute(name)
+    }
+
+    fn add_listener(&self, element: &web_sys::Node, listener: &Listener) {
+        listener.attach(element);
+    }
+
+    fn element(&self, node: &web_sys::Node) -> Option<Element> {
+        Some(to_element(node))
+    }
+}
+
+fn to_element(node: &web_sys::Node) -> Element {
+    node.clone().unchecked_into::<web_sys::Element>().into()
+}
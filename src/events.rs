@@ -0,0 +1,212 @@
+use super::diff::Path;
+use super::node::{Element, ListenerHandle, VirtualDomNode};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::EventTarget;
+
+/// An event handler stored on a virtual node.
+///
+/// Unlike a `Listener` it never touches the real DOM, the `VirtualDom` listens
+/// once per event type on its mount root and routes events to these handlers.
+#[derive(Clone)]
+pub struct Handler {
+    pub(crate) event: String,
+    pub(crate) capture: bool,
+    pub(crate) callback: Rc<dyn Fn(&SyntheticEvent)>,
+}
+
+impl fmt::Debug for Handler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handler: {} capture: {}", self.event, self.capture)
+    }
+}
+
+impl Handler {
+    /// Get the name of the event this handler handles, such as `"click"`.
+    #[inline]
+    pub fn event(&self) -> String {
+        self.event.clone()
+    }
+
+    /// Whether this handler runs in the capture phase, from the root down to the target
+    #[inline]
+    pub fn capture(&self) -> bool {
+        self.capture
+    }
+}
+
+/// The event passed to a `Handler`, wrapping the native event
+pub struct SyntheticEvent {
+    event: web_sys::Event,
+    current_target: Element,
+    propagation_stopped: Cell<bool>,
+}
+
+impl SyntheticEvent {
+    /// The native event that was caught on the mount root
+    pub fn event(&self) -> &web_sys::Event {
+        &self.event
+    }
+
+    /// The element whose handler is running right now
+    pub fn current_target(&self) -> Element {
+        self.current_target.clone()
+    }
+
+    /// Stops the event from reaching any further handler in the virtual tree.
+    /// The native event keeps going, so per-element listeners still see it.
+    pub fn stop_propagation(&self) {
+        self.propagation_stopped.set(true);
+    }
+
+    pub fn is_propagation_stopped(&self) -> bool {
+        self.propagation_stopped.get()
+    }
+}
+
+/// The handlers of the current virtual tree, by the path of their real DOM node
+#[derive(Default)]
+pub(crate) struct HandlerRegistry {
+    handlers: HashMap<Path, Vec<Handler>>,
+}
+
+impl HandlerRegistry {
+    /// Collects the handlers of `node`, which is rendered at `child_index` of the mount root
+    pub(crate) fn rebuild(&mut self, child_index: usize, node: &VirtualDomNode) {
+        self.handlers.clear();
+        self.collect(&Path::new(), child_index, node);
+    }
+
+    fn collect(&mut self, parent: &Path, child_index: usize, node: &VirtualDomNode) {
+        match node {
+            VirtualDomNode::ElementNode(vnode) => {
+                let mut path = parent.clone();
+                path.push(child_index);
+
+                let mut index = 0;
+                for child in vnode.children.iter() {
+                    self.collect(&path, index, child);
+                    index += child.dom_len();
+                }
+
+                if !vnode.handlers.is_empty() {
+                    self.handlers.insert(path, vnode.handlers.clone());
+                }
+            }
+            VirtualDomNode::Fragment(children) => {
+                let mut index = child_index;
+                for child in children.iter() {
+                    self.collect(parent, index, child);
+                    index += child.dom_len();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Every event type some handler is waiting for
+    pub(crate) fn event_types(&self) -> Vec<String> {
+        let mut event_types: Vec<String> = self
+            .handlers
+            .values()
+            .flat_map(|handlers| handlers.iter().map(|handler| handler.event.clone()))
+            .collect();
+        event_types.sort();
+        event_types.dedup();
+        event_types
+    }
+
+    /// The handlers for `event_type` along `path`, from the root down to the target
+    fn chain(&self, event_type: &str, path: &[usize]) -> Vec<(usize, Handler)> {
+        (1..=path.len())
+            .filter_map(|depth| Some((depth, self.handlers.get(&path[..depth])?)))
+            .flat_map(|(depth, handlers)| {
+                handlers
+                    .iter()
+                    .filter(|handler| handler.event == event_type)
+                    .map(move |handler| (depth, handler.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Listens for `event_type` on the mount root and runs the handlers of the
+/// virtual tree: capture handlers from the root down to the target, then the
+/// others bubbling back up, until one of them stops the propagation.
+///
+/// The root listens in the capture phase, so events that don't bubble, like
+/// `focus`, reach the handlers as well.
+pub(crate) fn listen_on_root(
+    root: &web_sys::Element,
+    event_type: &str,
+    registry: Rc<RefCell<HandlerRegistry>>,
+) -> ListenerHandle {
+    let root_node: web_sys::Node = root.clone().into();
+    let name = String::from(event_type);
+
+    let cb = Closure::wrap(Box::new(move |event: web_sys::Event| {
+        let target = match event
+            .target()
+            .and_then(|t| t.dyn_into::<web_sys::Node>().ok())
+        {
+            Some(target) => target,
+            None => return,
+        };
+        let (path, nodes) = match path_from_root(&root_node, &target) {
+            Some(found) => found,
+            None => return,
+        };
+
+        // cloned out, so the handlers are free to render again
+        let chain = registry.borrow().chain(&name, &path);
+        let capturing = chain.iter().filter(|(_, handler)| handler.capture);
+        let bubbling = chain.iter().rev().filter(|(_, handler)| !handler.capture);
+
+        let mut synthetic = SyntheticEvent {
+            event,
+            current_target: Element { el: None },
+            propagation_stopped: Cell::new(false),
+        };
+        for (depth, handler) in capturing.chain(bubbling) {
+            synthetic.current_target = nodes[*depth - 1]
+                .clone()
+                .unchecked_into::<web_sys::Element>()
+                .into();
+            (handler.callback)(&synthetic);
+            if synthetic.is_propagation_stopped() {
+                break;
+            }
+        }
+    }) as Box<dyn FnMut(_)>);
+
+    let target: EventTarget = root.clone().into();
+    ListenerHandle::new(target, event_type, cb, true)
+}
+
+/// The child indices from `root` down to `target`, along with the nodes on the way
+fn path_from_root(
+    root: &web_sys::Node,
+    target: &web_sys::Node,
+) -> Option<(Path, Vec<web_sys::Node>)> {
+    let mut path = Path::new();
+    let mut nodes = vec![];
+    let mut node = target.clone();
+
+    while !node.is_same_node(Some(root)) {
+        let parent = node.parent_node()?;
+        let siblings = parent.child_nodes();
+        let index =
+            (0..siblings.length()).position(|i| siblings.item(i).as_ref() == Some(&node))?;
+        path.push(index);
+        nodes.push(node);
+        node = parent;
+    }
+
+    path.reverse();
+    nodes.reverse();
+    Some((path, nodes))
+}
@@ -0,0 +1,245 @@
+use super::diff::Path;
+use super::dom::DomBackend;
+use super::node::{is_boolean_attribute, VirtualDomNode, VirtualElementNode};
+use super::render::{build_node, create_node_with, flatten_children};
+
+/// A place where the DOM being hydrated didn't match the virtual tree. Paths
+/// count DOM children from the root, like the paths of patches.
+///
+/// Every mismatch is repaired as it is found, so once hydrating is done the
+/// DOM matches the tree either way.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mismatch {
+    /// A node of another kind or tag, it was replaced with a new one
+    Node {
+        path: Path,
+        expected: String,
+        found: String,
+    },
+    /// A text node with other content, its text was changed
+    Text {
+        path: Path,
+        expected: String,
+        found: String,
+    },
+    /// An attribute that is missing, left over or has another value, it was set or removed
+    Attribute {
+        path: Path,
+        name: String,
+        expected: Option<String>,
+        found: Option<String>,
+    },
+    /// The DOM ran out of children before the tree did, the node was created
+    Missing { path: Path, expected: String },
+    /// A DOM child the tree has no node for, it was removed
+    Extra { path: Path, found: String },
+}
+
+/// Adopts the DOM below `root`, usually rendered by `ssr`, as the DOM of `node`
+/// instead of creating it again. Matching elements and text nodes are kept and
+/// get their listeners attached, everything else is repaired and reported.
+///
+/// Nodes are described as `<tag>`, `#text` or `#comment` in the mismatches.
+pub fn hydrate_with<B: DomBackend>(
+    dom: &B,
+    root: &B::Node,
+    node: &VirtualDomNode,
+) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    hydrate_children(dom, root, &[], std::slice::from_ref(node), &mut mismatches);
+    mismatches
+}
+
+fn hydrate_children<B: DomBackend>(
+    dom: &B,
+    parent: &B::Node,
+    parent_path: &[usize],
+    children: &[VirtualDomNode],
+    mismatches: &mut Vec<Mismatch>,
+) {
+    let flat = flatten_children(children);
+    for (index, child) in flat.iter().enumerate() {
+        let next_is_text = matches!(flat.get(index + 1), Some(VirtualDomNode::TextNode(_)));
+        hydrate_node(
+            dom,
+            parent,
+            child_path(parent_path, index),
+            child,
+            next_is_text,
+            mismatches,
+        );
+    }
+
+    // whatever is left was never rendered from this tree
+    while let Some(extra) = dom.child(parent, flat.len()) {
+        mismatches.push(Mismatch::Extra {
+            path: child_path(parent_path, flat.len()),
+            found: describe(dom, &extra),
+        });
+        dom.remove_child(parent, &extra);
+    }
+}
+
+/// Hydrates `node`, which is at the end of `path` among the children of `parent`
+fn hydrate_node<B: DomBackend>(
+    dom: &B,
+    parent: &B::Node,
+    path: Path,
+    node: &VirtualDomNode,
+    next_is_text: bool,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    let index = *path.last().expect("a child has a path");
+    let existing = dom.child(parent, index);
+
+    if let VirtualDomNode::TextNode(text_node) = node {
+        let found = existing.as_ref().and_then(|existing| dom.text(existing));
+        if text_node.text.is_empty() && found.as_deref() != Some("") {
+            // an empty text is written as nothing at all, so there is no node to adopt
+            let text = dom.create_text_node("");
+            dom.insert_before(parent, &text, existing.as_ref());
+            return;
+        }
+    }
+
+    let existing = match existing {
+        Some(existing) => existing,
+        None => {
+            mismatches.push(Mismatch::Missing {
+                path,
+                expected: describe_vnode(node),
+            });
+            create_node_with(dom, parent, node);
+            return;
+        }
+    };
+
+    match node {
+        VirtualDomNode::ElementNode(vnode)
+            if dom.tag_name(&existing).as_deref() == Some(vnode.node_type.as_str()) =>
+        {
+            hydrate_attributes(dom, &existing, &path, vnode, mismatches);
+            for listener in vnode.listeners.iter() {
+                dom.add_listener(&existing, listener);
+            }
+            hydrate_children(dom, &existing, &path, &vnode.children, mismatches);
+        }
+        VirtualDomNode::TextNode(text_node) if dom.text(&existing).is_some() => {
+            let found = dom.text(&existing).unwrap_or_default();
+            let text = &text_node.text;
+            if found == *text {
+                return;
+            }
+            if next_is_text && found.starts_with(text.as_str()) {
+                // adjacent texts come back from the HTML as one node, split this one off
+                let first = dom.create_text_node(text);
+                dom.insert_before(parent, &first, Some(&existing));
+                dom.set_text(&existing, &found[text.len()..]);
+                return;
+            }
+            mismatches.push(Mismatch::Text {
+                path,
+                expected: text.clone(),
+                found,
+            });
+            dom.set_text(&existing, text);
+        }
+        // the markup inside is used as the server rendered it
+        VirtualDomNode::RawHtml(raw)
+            if dom.tag_name(&existing).as_deref() == Some(raw.node_type.as_str()) => {}
+        VirtualDomNode::Empty if is_comment(dom, &existing) => {}
+        _ => {
+            mismatches.push(Mismatch::Node {
+                path,
+                expected: describe_vnode(node),
+                found: describe(dom, &existing),
+            });
+            if let Some(new_child) = build_node(dom, node) {
+                dom.replace_child(parent, &new_child, &existing);
+            }
+        }
+    }
+}
+
+fn hydrate_attributes<B: DomBackend>(
+    dom: &B,
+    el: &B::Node,
+    path: &[usize],
+    vnode: &VirtualElementNode,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    // attributes that must not be rendered shouldn't be in the DOM either
+    let rendered: Vec<(&str, &str)> = vnode
+        .attributes
+        .iter()
+        .filter_map(|attribute| Some((attribute.name.as_str(), attribute.rendered_value()?)))
+        .collect();
+
+    for &(name, value) in rendered.iter() {
+        let found = dom.attribute(el, name);
+
+        if is_boolean_attribute(&name.to_ascii_lowercase()) {
+            // the server writes boolean attributes without a value
+            if found.is_none() {
+                mismatches.push(Mismatch::Attribute {
+                    path: path.to_vec(),
+                    name: String::from(name),
+                    expected: Some(String::from(value)),
+                    found,
+                });
+                dom.set_attribute(el, name, value);
+            }
+        } else if found.as_deref() != Some(value) {
+            mismatches.push(Mismatch::Attribute {
+                path: path.to_vec(),
+                name: String::from(name),
+                expected: Some(String::from(value)),
+                found,
+            });
+            dom.set_attribute(el, name, value);
+        }
+    }
+
+    for name in dom.attribute_names(el) {
+        if rendered
+            .iter()
+            .all(|&(rendered_name, _)| rendered_name != name)
+        {
+            mismatches.push(Mismatch::Attribute {
+                path: path.to_vec(),
+                found: dom.attribute(el, &name),
+                name: name.clone(),
+                expected: None,
+            });
+            dom.remove_attribute(el, &name);
+        }
+    }
+}
+
+fn child_path(parent_path: &[usize], index: usize) -> Path {
+    let mut path = parent_path.to_vec();
+    path.push(index);
+    path
+}
+
+fn is_comment<B: DomBackend>(dom: &B, node: &B::Node) -> bool {
+    dom.tag_name(node).is_none() && dom.text(node).is_none()
+}
+
+fn describe<B: DomBackend>(dom: &B, node: &B::Node) -> String {
+    match dom.tag_name(node) {
+        Some(tag) => format!("<{}>", tag),
+        None if dom.text(node).is_some() => String::from("#text"),
+        None => String::from("#comment"),
+    }
+}
+
+fn describe_vnode(node: &VirtualDomNode) -> String {
+    match node {
+        VirtualDomNode::ElementNode(vnode) => format!("<{}>", vnode.node_type),
+        VirtualDomNode::TextNode(_) => String::from("#text"),
+        VirtualDomNode::RawHtml(raw) => format!("<{}>", raw.node_type),
+        VirtualDomNode::Fragment(_) => String::from("#fragment"),
+        VirtualDomNode::Empty => String::from("#comment"),
+    }
+}
@@ -1,8 +1,15 @@
+pub mod component;
+pub mod diff;
+pub mod dom;
+pub mod events;
+pub mod hydrate;
+pub mod memory;
 pub mod node;
+pub mod parse;
 pub mod render;
+pub mod sanitize;
+pub mod scheduler;
+pub mod ssr;
 pub mod vdom;
 
-use proc_macro_hack::proc_macro_hack;
-
-#[proc_macro_hack(support_nested)]
-pub use jsx_macro::html;
\ No newline at end of file
+pub use jsx_macro::html;
@@ -0,0 +1,303 @@
+use super::dom::DomBackend;
+use super::node::{is_raw_text_element, is_void_element};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+/// A DOM that lives in plain Rust memory, so rendering can be tested natively
+/// without a browser. It has no events, so listeners and lifecycle hooks don't run.
+///
+/// ```ignore
+/// let root = MemoryNode::element("div");
+/// let mut vdom = VirtualDom::new();
+/// vdom.render_with(&MemoryDom, &root, h("p", vec![t("hello")], vec![]));
+/// assert_eq!(root.inner_html(), "<p>hello</p>");
+/// ```
+pub struct MemoryDom;
+
+/// A node of the in-memory DOM, cloning it gives another handle to the same node
+#[derive(Clone)]
+pub struct MemoryNode(Rc<RefCell<NodeData>>);
+
+struct NodeData {
+    kind: NodeKind,
+    children: Vec<MemoryNode>,
+    parent: Weak<RefCell<NodeData>>,
+}
+
+enum NodeKind {
+    Element {
+        tag: String,
+        attributes: Vec<(String, String)>,
+    },
+    Text(String),
+    Comment,
+    /// HTML from `set_inner_html`, it is kept as a string instead of being parsed
+    Html(String),
+}
+
+impl MemoryNode {
+    fn new(kind: NodeKind) -> MemoryNode {
+        MemoryNode(Rc::new(RefCell::new(NodeData {
+            kind,
+            children: Vec::new(),
+            parent: Weak::new(),
+        })))
+    }
+
+    /// A new element that is in no tree yet, such as a root to render into
+    pub fn element(tag: &str) -> MemoryNode {
+        MemoryNode::new(NodeKind::Element {
+            tag: String::from(tag),
+            attributes: Vec::new(),
+        })
+    }
+
+    /// Whether both handles point at the same node
+    pub fn is_same_node(&self, other: &MemoryNode) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// The tag of an element, `None` for text and comments
+    pub fn tag_name(&self) -> Option<String> {
+        match &self.0.borrow().kind {
+            NodeKind::Element { tag, .. } => Some(tag.clone()),
+            _ => None,
+        }
+    }
+
+    /// The content of a text node
+    pub fn text(&self) -> Option<String> {
+        match &self.0.borrow().kind {
+            NodeKind::Text(text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        match &self.0.borrow().kind {
+            NodeKind::Element { attributes, .. } => attributes
+                .iter()
+                .find(|(attribute, _)| attribute == name)
+                .map(|(_, value)| value.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn children(&self) -> Vec<MemoryNode> {
+        self.0.borrow().children.clone()
+    }
+
+    pub fn parent(&self) -> Option<MemoryNode> {
+        self.0.borrow().parent.upgrade().map(MemoryNode)
+    }
+
+    /// The HTML of the node's children, like the browser's `innerHTML`
+    pub fn inner_html(&self) -> String {
+        let mut html = String::new();
+        for child in self.0.borrow().children.iter() {
+            child.write_html(&mut html);
+        }
+        html
+    }
+
+    /// The HTML of the node itself, like the browser's `outerHTML`
+    pub fn outer_html(&self) -> String {
+        let mut html = String::new();
+        self.write_html(&mut html);
+        html
+    }
+
+    fn write_html(&self, html: &mut String) {
+        let data = self.0.borrow();
+        match &data.kind {
+            NodeKind::Element { tag, attributes } => {
+                html.push('<');
+                html.push_str(tag);
+                for (name, value) in attributes.iter() {
+                    html.push(' ');
+                    html.push_str(name);
+                    html.push_str("=\"");
+                    escape(value, true, html);
+                    html.push('"');
+                }
+                html.push('>');
+                if !is_void_element(tag) {
+                    for child in data.children.iter() {
+                        // the browser doesn't escape the text in a script or style either
+                        match &child.0.borrow().kind {
+                            NodeKind::Text(text) if is_raw_text_element(tag) => html.push_str(text),
+                            _ => child.write_html(html),
+                        }
+                    }
+                    html.push_str("</");
+                    html.push_str(tag);
+                    html.push('>');
+                }
+            }
+            NodeKind::Text(text) => escape(text, false, html),
+            NodeKind::Comment => html.push_str("<!---->"),
+            NodeKind::Html(raw) => html.push_str(raw),
+        }
+    }
+
+    /// Takes the node out of its parent, if it has one
+    fn detach(&self) {
+        let parent = self.0.borrow_mut().parent.upgrade();
+        if let Some(parent) = parent {
+            parent
+                .borrow_mut()
+                .children
+                .retain(|child| !child.is_same_node(self));
+            self.0.borrow_mut().parent = Weak::new();
+        }
+    }
+}
+
+impl fmt::Debug for MemoryNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MemoryNode: {}", self.outer_html())
+    }
+}
+
+/// Escapes text the way the browser serializes it, attribute values also get their quotes escaped
+fn escape(text: &str, attribute: bool, html: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '"' if attribute => html.push_str("&quot;"),
+            '<' if !attribute => html.push_str("&lt;"),
+            '>' if !attribute => html.push_str("&gt;"),
+            c => html.push(c),
+        }
+    }
+}
+
+impl DomBackend for MemoryDom {
+    type Node = MemoryNode;
+
+    fn create_element(&self, tag: &str) -> MemoryNode {
+        MemoryNode::element(tag)
+    }
+
+    fn create_text_node(&self, text: &str) -> MemoryNode {
+        MemoryNode::new(NodeKind::Text(String::from(text)))
+    }
+
+    fn create_placeholder(&self) -> MemoryNode {
+        MemoryNode::new(NodeKind::Comment)
+    }
+
+    fn child(&self, parent: &MemoryNode, index: usize) -> Option<MemoryNode> {
+        parent.0.borrow().children.get(index).cloned()
+    }
+
+    fn insert_before(
+        &self,
+        parent: &MemoryNode,
+        child: &MemoryNode,
+        reference: Option<&MemoryNode>,
+    ) {
+        if let Some(reference) = reference {
+            // inserting a node right before itself leaves it where it is
+            if reference.is_same_node(child) {
+                return;
+            }
+        }
+        child.detach();
+
+        let mut data = parent.0.borrow_mut();
+        let index = reference
+            .and_then(|reference| {
+                data.children
+                    .iter()
+                    .position(|sibling| sibling.is_same_node(reference))
+            })
+            .unwrap_or_else(|| data.children.len());
+        data.children.insert(index, child.clone());
+        child.0.borrow_mut().parent = Rc::downgrade(&parent.0);
+    }
+
+    fn remove_child(&self, parent: &MemoryNode, child: &MemoryNode) {
+        if let Some(child_parent) = child.parent() {
+            if child_parent.is_same_node(parent) {
+                child.detach();
+            }
+        }
+    }
+
+    fn replace_child(&self, parent: &MemoryNode, new_child: &MemoryNode, old_child: &MemoryNode) {
+        if new_child.is_same_node(old_child) {
+            return;
+        }
+        new_child.detach();
+
+        let mut data = parent.0.borrow_mut();
+        let index = data
+            .children
+            .iter()
+            .position(|sibling| sibling.is_same_node(old_child));
+        if let Some(index) = index {
+            data.children[index] = new_child.clone();
+            old_child.0.borrow_mut().parent = Weak::new();
+            new_child.0.borrow_mut().parent = Rc::downgrade(&parent.0);
+        }
+    }
+
+    fn set_text(&self, node: &MemoryNode, text: &str) {
+        if let NodeKind::Text(content) = &mut node.0.borrow_mut().kind {
+            *content = String::from(text);
+        }
+    }
+
+    fn set_attribute(&self, element: &MemoryNode, name: &str, value: &str) {
+        if let NodeKind::Element { attributes, .. } = &mut element.0.borrow_mut().kind {
+            match attributes
+                .iter_mut()
+                .find(|(attribute, _)| attribute == name)
+            {
+                Some(attribute) => attribute.1 = String::from(value),
+                None => attributes.push((String::from(name), String::from(value))),
+            }
+        }
+    }
+
+    fn remove_attribute(&self, element: &MemoryNode, name: &str) {
+        if let NodeKind::Element { attributes, .. } = &mut element.0.borrow_mut().kind {
+            attributes.retain(|(attribute, _)| attribute != name);
+        }
+    }
+
+    fn set_inner_html(&self, element: &MemoryNode, html: &str) {
+        for child in element.children() {
+            child.detach();
+        }
+        if !html.is_empty() {
+            self.append_child(
+                element,
+                &MemoryNode::new(NodeKind::Html(String::from(html))),
+            );
+        }
+    }
+
+    fn tag_name(&self, node: &MemoryNode) -> Option<String> {
+        node.tag_name()
+    }
+
+    fn text(&self, node: &MemoryNode) -> Option<String> {
+        node.text()
+    }
+
+    fn attribute_names(&self, element: &MemoryNode) -> Vec<String> {
+        match &element.0.borrow().kind {
+            NodeKind::Element { attributes, .. } => {
+                attributes.iter().map(|(name, _)| name.clone()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn attribute(&self, element: &MemoryNode, name: &str) -> Option<String> {
+        element.attribute(name)
+    }
+}
@@ -1,4 +1,9 @@
+use super::component::Lifecycle;
+use super::events::Handler;
+use super::render::attr;
+use std::cell::RefCell;
 use std::fmt;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::EventTarget;
@@ -14,12 +19,24 @@ impl From<web_sys::Node> for Node {
     }
 }
 
+impl From<Element> for Node {
+    fn from(obj: Element) -> Node {
+        Node {
+            node: obj.el.map(|el| el.into()),
+        }
+    }
+}
+
 /// VirtualElementNode represents an html element
 //#[derive(Debug, Clone)]
 pub struct VirtualElementNode {
+    pub key: NodeKey,
     pub node_type: String,
     pub children: Vec<VirtualDomNode>,
     pub attributes: Vec<Attribute>,
+    pub listeners: Vec<Listener>,
+    pub handlers: Vec<Handler>,
+    pub hooks: Option<Rc<dyn Lifecycle>>,
 }
 
 impl fmt::Debug for VirtualElementNode {
@@ -39,13 +56,76 @@ pub struct VirtualTextNode {
     pub text: String,
 }
 
+/// VirtualRawHtml is trusted markup inside a container element, see
+/// `render::dangerously_set_inner_html`
+#[derive(Debug, Clone)]
+pub struct VirtualRawHtml {
+    pub node_type: String,
+    pub html: String,
+}
+
 /// We use an enumeration to represent these two plus an empty DOM node to represent nothing
-//#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum VirtualDomNode {
     Empty,
     ElementNode(VirtualElementNode),
     TextNode(VirtualTextNode),
+    /// Several sibling nodes without a wrapper element, they are flattened into the parent
+    Fragment(Vec<VirtualDomNode>),
+    /// An element whose content is set from a string of HTML, it is only
+    /// compared by that string and the DOM inside it is never diffed
+    RawHtml(VirtualRawHtml),
+}
+
+impl VirtualDomNode {
+    /// The key of this node, only element nodes can be keyed
+    #[inline]
+    pub fn key(&self) -> NodeKey {
+        match self {
+            VirtualDomNode::ElementNode(vnode) => vnode.key,
+            _ => NodeKey::NONE,
+        }
+    }
+
+    /// How many real DOM nodes this node is rendered as
+    pub(crate) fn dom_len(&self) -> usize {
+        match self {
+            VirtualDomNode::Fragment(children) => children.iter().map(|c| c.dom_len()).sum(),
+            _ => 1,
+        }
+    }
 }
+
+impl From<String> for VirtualDomNode {
+    fn from(text: String) -> VirtualDomNode {
+        VirtualDomNode::TextNode(VirtualTextNode { text })
+    }
+}
+
+impl<'a> From<&'a str> for VirtualDomNode {
+    fn from(text: &'a str) -> VirtualDomNode {
+        VirtualDomNode::TextNode(VirtualTextNode {
+            text: String::from(text),
+        })
+    }
+}
+
+impl<'a> From<&'a String> for VirtualDomNode {
+    fn from(text: &'a String) -> VirtualDomNode {
+        VirtualDomNode::TextNode(VirtualTextNode { text: text.clone() })
+    }
+}
+
+/// `None` renders as an empty node, so it keeps its place among its siblings
+impl<T: Into<VirtualDomNode>> From<Option<T>> for VirtualDomNode {
+    fn from(node: Option<T>) -> VirtualDomNode {
+        match node {
+            Some(node) => node.into(),
+            None => VirtualDomNode::Empty,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Element {
     pub el: Option<web_sys::Element>,
@@ -94,7 +174,22 @@ impl Element {
         }
     }
 
+    /// Sets an attribute with the same checks as rendering, see
+    /// `Attribute::rendered_value`. An event handler given as a string is an
+    /// error, and a boolean attribute set to `"false"` is removed.
     pub fn set_attribute(&self, name: &str, value: &str) -> Result<(), JsValue> {
+        match attr(name, value).rendered_value() {
+            Some(value) => self.dangerously_set_attribute(name, value),
+            None if is_event_handler_attribute(name) => Err(JsValue::from_str(&format!(
+                "refusing to set the event handler attribute `{}`, add a listener instead",
+                name
+            ))),
+            None => self.remove_attribute(name),
+        }
+    }
+
+    /// Sets an attribute exactly as given, for trusted values only
+    pub fn dangerously_set_attribute(&self, name: &str, value: &str) -> Result<(), JsValue> {
         if let Some(el) = self.el.as_ref() {
             el.set_attribute(name, value)
         } else {
@@ -102,6 +197,23 @@ impl Element {
         }
     }
 
+    /// Sets a property on the underlying JS object, such as `input.value`
+    pub fn set_property(&self, name: &str, value: &JsValue) -> Result<(), JsValue> {
+        if let Some(el) = self.el.as_ref() {
+            js_sys::Reflect::set(el.as_ref(), &JsValue::from_str(name), value).map(|_| ())
+        } else {
+            Err(JsValue::null())
+        }
+    }
+
+    pub fn remove_attribute(&self, name: &str) -> Result<(), JsValue> {
+        if let Some(el) = self.el.as_ref() {
+            el.remove_attribute(name)
+        } else {
+            Err(JsValue::null())
+        }
+    }
+
     pub fn query_selector(selector: &str) -> Option<Element> {
         let body: web_sys::Element = web_sys::window()?.document()?.body()?.into();
         let el = body.query_selector(selector).ok()?;
@@ -137,6 +249,7 @@ impl Element {
             }
         }
     }
+
     /// Gets the text content of the `self.el` element
     pub fn text_content(&mut self) -> Option<String> {
         let mut text = None;
@@ -170,81 +283,72 @@ impl Element {
         }
     }
 
-    pub fn add_event_listener<T>(&mut self, event_name: &str, handler: T)
+    /// Adds `handler` as a listener for `event_name`, it stays attached until the
+    /// returned handle is dropped
+    #[must_use = "the listener is removed when the handle is dropped"]
+    pub fn add_event_listener<T>(&mut self, event_name: &str, handler: T) -> Option<ListenerHandle>
     where
         T: 'static + FnMut(web_sys::Event),
     {
         let cb = Closure::wrap(Box::new(handler) as Box<dyn FnMut(_)>);
-        if let Some(el) = self.el.take() {
-            let el_et: EventTarget = el.into();
-            el_et
-                .add_event_listener_with_callback(event_name, cb.as_ref().unchecked_ref())
-                .unwrap();
-            cb.forget();
-
-            if let Ok(el) = el_et.dyn_into::<web_sys::Element>() {
-                self.el = Some(el);
-            }
-        }
+        let el_et: EventTarget = self.el.clone()?.into();
+        Some(ListenerHandle::new(el_et, event_name, cb, false))
     }
 
-
-    /// Delegate an event to a selector
-    pub fn delegate<T>(
+    /// Delegate an event to the descendants of `self.el` matching `selector`.
+    ///
+    /// The handler runs when the event starts on a matching element or anywhere
+    /// inside one, and gets that matching element next to the event. The
+    /// listener stays attached until the returned handle is dropped.
+    ///
+    /// ```ignore
+    /// let handle = list.delegate("li.item", "click", |_event, item| {
+    ///     item.class_list_add("selected");
+    /// }, false);
+    /// ```
+    #[must_use = "the listener is removed when the handle is dropped"]
+    pub fn delegate<S, T>(
         &mut self,
-        selector: &'static str,
+        selector: S,
         event: &str,
         mut handler: T,
         use_capture: bool,
-    ) where
-        T: 'static + FnMut(web_sys::Event) -> (),
+    ) -> Option<ListenerHandle>
+    where
+        S: Into<String>,
+        T: 'static + FnMut(web_sys::Event, Element),
     {
-        let el = match self.el.take() {
-            Some(e) => e,
-            None => return,
-        };
-        if let Some(dyn_el) = &el.dyn_ref::<EventTarget>() {
-            if let Some(window) = web_sys::window() {
-                if let Some(document) = window.document() {
-                    // TODO document selector to the target element
-                    let tg_el = document;
-
-                    let cb = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                        if let Some(target_element) = event.target() {
-                            let dyn_target_el: Option<&web_sys::Node> =
-                                wasm_bindgen::JsCast::dyn_ref(&target_element);
-                            if let Some(target_element) = dyn_target_el {
-                                if let Ok(potential_elements) = tg_el.query_selector_all(selector) {
-                                    let mut has_match = false;
-                                    for i in 0..potential_elements.length() {
-                                        if let Some(el) = potential_elements.get(i) {
-                                            if target_element.is_equal_node(Some(&el)) {
-                                                has_match = true;
-                                            }
-                                            break;
-                                        }
-                                    }
-
-                                    if has_match {
-                                        handler(event);
-                                    }
-                                }
-                            }
-                        }
-                    }) as Box<dyn FnMut(_)>);
-
-                    dyn_el
-                        .add_event_listener_with_callback_and_bool(
-                            event,
-                            cb.as_ref().unchecked_ref(),
-                            use_capture,
-                        )
-                        .unwrap();
-                    cb.forget(); // TODO cycle collect
-                }
+        let selector = selector.into();
+        let root = self.el.clone()?;
+        let target: EventTarget = root.clone().into();
+
+        let cb = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            // text nodes can't match a selector, start from their parent element
+            let start = match event.target() {
+                Some(target) => match target.dyn_into::<web_sys::Element>() {
+                    Ok(el) => Some(el),
+                    Err(target) => target
+                        .dyn_into::<web_sys::Node>()
+                        .ok()
+                        .and_then(|node| node.parent_element()),
+                },
+                None => None,
+            };
+
+            // the closest ancestor matching the selector, as long as it is inside `root`
+            let matched = start
+                .and_then(|el| el.closest(&selector).ok())
+                .and_then(|matched| matched)
+                .filter(|matched| {
+                    root.contains(Some(matched)) && !root.is_same_node(Some(matched))
+                });
+
+            if let Some(matched) = matched {
+                handler(event, matched.into());
             }
-        }
-        self.el = Some(el);
+        }) as Box<dyn FnMut(_)>);
+
+        Some(ListenerHandle::new(target, event, cb, use_capture))
     }
 
     /// Removes a class list item from the element
@@ -282,6 +386,117 @@ impl Element {
 pub struct Attribute {
     pub(crate) name: String,
     pub(crate) value: String,
+    /// Made with `dangerous_attr`, so the value is rendered without any checks
+    pub(crate) trusted: bool,
+}
+
+/// Whether an attribute with this name is "volatile", see `Attribute::is_volatile`
+#[inline]
+pub fn is_volatile_attribute(name: &str) -> bool {
+    matches!(name, "value" | "checked" | "selected")
+}
+
+/// Whether an attribute with this name is a boolean attribute, which is on by
+/// being present, like `disabled`. A value of `"false"` leaves it out, see
+/// `Attribute::rendered_value`.
+#[inline]
+pub fn is_boolean_attribute(name: &str) -> bool {
+    matches!(
+        name,
+        "allowfullscreen"
+            | "async"
+            | "autofocus"
+            | "autoplay"
+            | "checked"
+            | "controls"
+            | "default"
+            | "defer"
+            | "disabled"
+            | "formnovalidate"
+            | "hidden"
+            | "ismap"
+            | "loop"
+            | "multiple"
+            | "muted"
+            | "nomodule"
+            | "novalidate"
+            | "open"
+            | "readonly"
+            | "required"
+            | "reversed"
+            | "selected"
+    )
+}
+
+/// Whether an attribute with this name holds a URL that the browser loads or
+/// navigates to, so a `javascript:` URL in it would run
+#[inline]
+pub fn is_url_attribute(name: &str) -> bool {
+    matches!(
+        name,
+        "action"
+            | "background"
+            | "cite"
+            | "data"
+            | "formaction"
+            | "href"
+            | "longdesc"
+            | "poster"
+            | "src"
+            | "xlink:href"
+    )
+}
+
+/// Whether an attribute with this name is an inline event handler like
+/// `onclick`, whose value the browser runs as a script
+#[inline]
+pub fn is_event_handler_attribute(name: &str) -> bool {
+    name.len() > 2 && name.as_bytes()[..2].eq_ignore_ascii_case(b"on")
+}
+
+/// Whether the browser reads `url` as a URL with `scheme`, such as `"javascript:"`.
+/// Like the browser it ignores case, leading whitespace and control characters,
+/// and tabs and newlines anywhere in the scheme.
+pub(crate) fn has_url_scheme(url: &str, scheme: &str) -> bool {
+    let start: String = url
+        .trim_start_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .take(scheme.len())
+        .collect();
+    start.eq_ignore_ascii_case(scheme)
+}
+
+/// What a URL that is not allowed to render is replaced with
+const BLOCKED_URL: &str = "about:blank";
+
+/// Whether an element with this tag can't have children, so it is written without a closing tag
+#[inline]
+pub fn is_void_element(tag: &str) -> bool {
+    matches!(
+        tag,
+        "area"
+            | "base"
+            | "br"
+            | "col"
+            | "embed"
+            | "hr"
+            | "img"
+            | "input"
+            | "link"
+            | "meta"
+            | "param"
+            | "source"
+            | "track"
+            | "wbr"
+    )
+}
+
+/// Whether the text in an element with this tag is raw text, which the browser
+/// doesn't decode and which ends at the first closing tag
+#[inline]
+pub fn is_raw_text_element(tag: &str) -> bool {
+    tag.eq_ignore_ascii_case("script") || tag.eq_ignore_ascii_case("style")
 }
 
 impl Attribute {
@@ -296,37 +511,165 @@ impl Attribute {
     pub fn value(&self) -> String {
         self.value.clone()
     }
-    /*
+
+    /// The value this attribute gets in the DOM and in server-rendered HTML,
+    /// `None` when it must not be rendered at all.
+    ///
+    /// A boolean attribute like `disabled` with the value `"false"` is left out.
+    /// Attribute values often come from users, so unless the attribute was made
+    /// with `dangerous_attr`, event handlers given as strings like `onclick="..."`
+    /// are left out too, and `javascript:` and `data:` URLs in attributes like
+    /// `href` or `src` are replaced with `about:blank`.
+    pub fn rendered_value(&self) -> Option<&str> {
+        let name = self.name.to_ascii_lowercase();
+        if is_boolean_attribute(&name) && self.value == "false" {
+            return None;
+        }
+        if self.trusted {
+            return Some(&self.value);
+        }
+        if is_event_handler_attribute(&self.name) {
+            return None;
+        }
+        if is_url_attribute(&name)
+            && (has_url_scheme(&self.value, "javascript:") || has_url_scheme(&self.value, "data:"))
+        {
+            return Some(BLOCKED_URL);
+        }
+        Some(&self.value)
+    }
+
     /// Certain attributes are considered "volatile" and can change via user
     /// input that we can't see when diffing against the old virtual DOM. For
     /// these attributes, we want to always re-set the attribute on the physical
     /// DOM node, even if the old and new virtual DOM nodes have the same value.
     #[inline]
-    pub(crate) fn _is_volatile(&self) -> bool {
-        match self.name {
-            "value" | "checked" | "selected" => true,
-            _ => false,
+    pub fn is_volatile(&self) -> bool {
+        is_volatile_attribute(&self.name)
+    }
+}
+
+/// What a `Listener` calls for every event
+pub(crate) type Callback = Rc<dyn Fn(web_sys::Event)>;
+
+/// An event listener on a DOM node, such as `onclick={move |e| ...}`.
+///
+/// The real DOM node gets a single JS closure per listener that calls whatever
+/// callback is current, so a re-render only swaps the callback and leaves the
+/// DOM alone.
+pub struct Listener {
+    pub(crate) event: String,
+    pub(crate) callback: Callback,
+    pub(crate) attached: RefCell<Option<AttachedListener>>,
+}
+
+/// The JS side of a `Listener` that is attached to a real DOM node
+pub(crate) struct AttachedListener {
+    current: Rc<RefCell<Callback>>,
+    _handle: ListenerHandle,
+}
+
+/// Owns the JS closure of an event listener. The listener is removed from its
+/// target and the closure is freed when the handle is dropped.
+#[must_use = "the listener is removed as soon as the handle is dropped"]
+pub struct ListenerHandle {
+    target: EventTarget,
+    event: String,
+    closure: Closure<dyn FnMut(web_sys::Event)>,
+    use_capture: bool,
+}
+
+impl ListenerHandle {
+    pub(crate) fn new(
+        target: EventTarget,
+        event: &str,
+        closure: Closure<dyn FnMut(web_sys::Event)>,
+        use_capture: bool,
+    ) -> ListenerHandle {
+        target
+            .add_event_listener_with_callback_and_bool(
+                event,
+                closure.as_ref().unchecked_ref(),
+                use_capture,
+            )
+            .unwrap();
+        ListenerHandle {
+            target,
+            event: String::from(event),
+            closure,
+            use_capture,
         }
     }
-    */
+
+    /// Keeps the listener attached for the rest of the page's life, its closure is never freed
+    pub fn forget(self) {
+        std::mem::forget(self);
+    }
 }
 
-/*
+impl Drop for ListenerHandle {
+    fn drop(&mut self) {
+        let _res = self.target.remove_event_listener_with_callback_and_bool(
+            &self.event,
+            self.closure.as_ref().unchecked_ref(),
+            self.use_capture,
+        );
+    }
+}
 
-    #[derive(Debug, Clone)]
-    pub(crate) enum NodeKind<'a> {
-        Element(&'a ElementNode<'a>),
+impl fmt::Debug for ListenerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ListenerHandle: {}", self.event)
     }
+}
 
-    #[derive(Debug, Clone)]
-    pub(crate) struct ElementNode<'a> {
-        pub key: NodeKey,
-        pub tag_name: &'a str,
-        pub attributes: &'a [Attribute<'a>],
-        pub children: &'a [Node<'a>],
-        pub namespace: Option<&'a str>,
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Listener: {}", self.event)
+    }
+}
+
+impl Listener {
+    /// Get the name of the event this listener handles, such as `"click"`.
+    #[inline]
+    pub fn event(&self) -> String {
+        self.event.clone()
+    }
+
+    /// Adds a JS closure calling this listener's callback to `target`
+    pub(crate) fn attach(&self, target: &EventTarget) {
+        let current = Rc::new(RefCell::new(self.callback.clone()));
+        let callback = current.clone();
+        let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            let callback = callback.borrow().clone();
+            callback(event);
+        }) as Box<dyn FnMut(_)>);
+
+        let handle = ListenerHandle::new(target.clone(), &self.event, closure, false);
+        *self.attached.borrow_mut() = Some(AttachedListener {
+            current,
+            _handle: handle,
+        });
+    }
+
+    /// Removes this listener's JS closure from the node it was attached to
+    pub(crate) fn detach(&self) {
+        self.attached.borrow_mut().take();
     }
 
+    /// Hands the JS closure attached for `self` over to `new`, which from now on
+    /// gets called instead. Returns `false` when there was nothing to hand over.
+    pub(crate) fn transfer_to(&self, new: &Listener) -> bool {
+        match self.attached.borrow_mut().take() {
+            Some(attached) => {
+                *attached.current.borrow_mut() = new.callback.clone();
+                *new.attached.borrow_mut() = Some(attached);
+                true
+            }
+            None => false,
+        }
+    }
+}
 
 /// The key for keyed children.
 ///
@@ -368,6 +711,23 @@ impl NodeKey {
     }
 }
 
+/*
+
+    #[derive(Debug, Clone)]
+    pub(crate) enum NodeKind<'a> {
+        Element(&'a ElementNode<'a>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct ElementNode<'a> {
+        pub key: NodeKey,
+        pub tag_name: &'a str,
+        pub attributes: &'a [Attribute<'a>],
+        pub children: &'a [Node<'a>],
+        pub namespace: Option<&'a str>,
+    }
+
+
 impl<'a> Node<'a> {
     /// Low-level constructor for making a new `Node` of type element with given
     /// parts.
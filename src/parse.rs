@@ -0,0 +1,356 @@
+use super::node::{is_raw_text_element, is_void_element, Attribute, VirtualDomNode};
+use super::render::{attr, h, t};
+use htmlescape::decode_html;
+use std::error::Error;
+use std::fmt;
+
+/// Parses a piece of HTML, such as content from a CMS, into virtual nodes that
+/// can be put in a tree and diffed like any other.
+///
+/// Tag and attribute names are lowercased and entities in text and attribute
+/// values are decoded. Void elements need no closing tag, any other element
+/// can close itself with `/>`. Comments and doctypes are left out, and the
+/// content of `<script>` and `<style>` is kept as it is. Elements can be
+/// nested `MAX_DEPTH` deep, like in the browser.
+///
+/// ```ignore
+/// let nodes = parse_html(r#"<p class="intro">Fish &amp; chips</p>"#)?;
+/// vdom.render(&mut root, h("article", vec![fragment(nodes)], vec![]));
+/// ```
+pub fn parse_html(html: &str) -> Result<Vec<VirtualDomNode>, ParseError> {
+    let mut parser = Parser { html, position: 0 };
+    parser.parse_nodes()
+}
+
+/// How deep elements can be nested in parsed HTML, which is also where the
+/// browser stops nesting them. Rendering, diffing and dropping a tree recurse
+/// once per level, so deeper HTML is an error rather than a stack overflow later.
+pub const MAX_DEPTH: usize = 512;
+
+/// Why some HTML couldn't be parsed, and where
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// The line the problem is on, counting from 1
+    pub line: usize,
+    /// The character in that line where the problem starts, counting from 1
+    pub column: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseErrorKind {
+    /// The HTML ended inside a tag, a comment or an attribute value
+    UnexpectedEnd,
+    /// An element that is never closed, with its tag
+    UnclosedElement(String),
+    /// A closing tag that doesn't close the innermost open element, with its tag
+    UnexpectedClosingTag(String),
+    /// A character in a tag where an attribute name was expected
+    InvalidAttribute(char),
+    /// An entity such as `&nosuch;` that doesn't stand for a character
+    InvalidEntity(String),
+    /// An element nested deeper than `MAX_DEPTH`
+    TooDeep,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of the HTML")?,
+            ParseErrorKind::UnclosedElement(tag) => write!(f, "<{}> is never closed", tag)?,
+            ParseErrorKind::UnexpectedClosingTag(tag) => {
+                write!(f, "</{}> doesn't close an open element", tag)?
+            }
+            ParseErrorKind::InvalidAttribute(c) => write!(f, "unexpected {:?} in a tag", c)?,
+            ParseErrorKind::InvalidEntity(entity) => write!(f, "unknown entity {}", entity)?,
+            ParseErrorKind::TooDeep => write!(f, "elements nested more than {} deep", MAX_DEPTH)?,
+        }
+        write!(f, " at line {}, column {}", self.line, self.column)
+    }
+}
+
+impl Error for ParseError {}
+
+struct Parser<'a> {
+    html: &'a str,
+    /// Byte offset of the next character to parse
+    position: usize,
+}
+
+/// An element whose closing tag hasn't been parsed yet
+struct OpenElement {
+    tag: String,
+    attributes: Vec<Attribute>,
+    /// Byte offset of its opening tag
+    opened_at: usize,
+    children: Vec<VirtualDomNode>,
+}
+
+/// What `Parser::parse_element` found
+enum Parsed {
+    /// A whole element
+    Node(VirtualDomNode),
+    /// An element whose children come next
+    Open(OpenElement),
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.html[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        if self.rest().starts_with(prefix) {
+            self.position += prefix.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Takes everything up to the first character that doesn't match `f`
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let rest = self.rest();
+        let end = rest.find(|c: char| !f(c)).unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+
+    fn error(&self, kind: ParseErrorKind, position: usize) -> ParseError {
+        let before = &self.html[..position];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        ParseError {
+            kind,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// Parses all the nodes in the HTML. Open elements are kept on a stack
+    /// rather than in nested calls, so deep HTML can't overflow the call stack.
+    fn parse_nodes(&mut self) -> Result<Vec<VirtualDomNode>, ParseError> {
+        let mut nodes = Vec::new();
+        let mut open: Vec<OpenElement> = Vec::new();
+        loop {
+            let start = self.position;
+            let rest = self.rest();
+
+            let node = if rest.is_empty() {
+                return match open.pop() {
+                    Some(element) => Err(self.error(
+                        ParseErrorKind::UnclosedElement(element.tag),
+                        element.opened_at,
+                    )),
+                    None => Ok(nodes),
+                };
+            } else if rest.starts_with("</") {
+                self.position += 2;
+                let tag = self.parse_name();
+                self.skip_whitespace();
+                if !self.eat(">") {
+                    return Err(self.error(ParseErrorKind::UnexpectedEnd, self.position));
+                }
+                match open.pop() {
+                    Some(element) if element.tag == tag => {
+                        h(&element.tag, element.children, element.attributes)
+                    }
+                    _ => return Err(self.error(ParseErrorKind::UnexpectedClosingTag(tag), start)),
+                }
+            } else if self.eat("<!--") {
+                match self.rest().find("-->") {
+                    Some(end) => self.position += end + 3,
+                    None => return Err(self.error(ParseErrorKind::UnexpectedEnd, start)),
+                }
+                continue;
+            } else if self.eat("<!") || self.eat("<?") {
+                // doctypes and processing instructions have no node
+                match self.rest().find('>') {
+                    Some(end) => self.position += end + 1,
+                    None => return Err(self.error(ParseErrorKind::UnexpectedEnd, start)),
+                }
+                continue;
+            } else if starts_opening_tag(rest) {
+                match self.parse_element()? {
+                    Parsed::Node(node) => node,
+                    Parsed::Open(element) => {
+                        if open.len() == MAX_DEPTH {
+                            return Err(self.error(ParseErrorKind::TooDeep, element.opened_at));
+                        }
+                        open.push(element);
+                        continue;
+                    }
+                }
+            } else {
+                // a `<` that doesn't start any markup is just text
+                let end = rest
+                    .char_indices()
+                    .skip(1)
+                    .find(|(i, _)| starts_markup(&rest[*i..]))
+                    .map_or(rest.len(), |(i, _)| i);
+                let text = self.decode(&rest[..end], start)?;
+                self.position += end;
+                t(&text)
+            };
+
+            match open.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => nodes.push(node),
+            }
+        }
+    }
+
+    /// Parses an opening tag, and the whole element when it can't have
+    /// children or its content is raw text
+    fn parse_element(&mut self) -> Result<Parsed, ParseError> {
+        let opened_at = self.position;
+        self.position += 1;
+        let tag = self.parse_name();
+
+        let mut attributes: Vec<Attribute> = Vec::new();
+        let self_closing = loop {
+            self.skip_whitespace();
+            if self.eat(">") {
+                break false;
+            }
+            if self.eat("/>") {
+                break true;
+            }
+            let name_at = self.position;
+            let name = self.parse_name();
+            if name.is_empty() {
+                return Err(match self.peek() {
+                    Some(c) => self.error(ParseErrorKind::InvalidAttribute(c), name_at),
+                    None => self.error(ParseErrorKind::UnexpectedEnd, name_at),
+                });
+            }
+            let value = self.parse_attribute_value()?;
+            // like the browser, the first of two attributes with the same name wins
+            if attributes.iter().all(|attribute| attribute.name != name) {
+                attributes.push(attr(&name, &value));
+            }
+        };
+
+        let children = if self_closing || is_void_element(&tag) {
+            vec![]
+        } else if is_raw_text_element(&tag) {
+            // raw text, it ends at the first closing tag and nothing in it is decoded
+            let closing = format!("</{}", tag);
+            let end = self
+                .rest()
+                .to_ascii_lowercase()
+                .find(&closing)
+                .ok_or_else(|| {
+                    self.error(ParseErrorKind::UnclosedElement(tag.clone()), opened_at)
+                })?;
+            let text = &self.rest()[..end];
+            self.position += end + closing.len();
+            self.skip_whitespace();
+            if !self.eat(">") {
+                return Err(self.error(ParseErrorKind::UnexpectedEnd, self.position));
+            }
+            if text.is_empty() {
+                vec![]
+            } else {
+                vec![t(text)]
+            }
+        } else {
+            return Ok(Parsed::Open(OpenElement {
+                tag,
+                attributes,
+                opened_at,
+                children: Vec::new(),
+            }));
+        };
+
+        Ok(Parsed::Node(h(&tag, children, attributes)))
+    }
+
+    /// A tag or attribute name, lowercased
+    fn parse_name(&mut self) -> String {
+        self.take_while(|c| !c.is_whitespace() && !"/>=\"'<".contains(c))
+            .to_lowercase()
+    }
+
+    /// The value after an attribute name, an attribute without one has an empty value
+    fn parse_attribute_value(&mut self) -> Result<String, ParseError> {
+        let before_equals = self.position;
+        self.skip_whitespace();
+        if !self.eat("=") {
+            self.position = before_equals;
+            return Ok(String::new());
+        }
+        self.skip_whitespace();
+
+        let start = self.position;
+        let raw = match self.peek() {
+            Some(quote) if quote == '"' || quote == '\'' => {
+                self.position += 1;
+                let value = self.take_while(|c| c != quote);
+                if !self.eat(&quote.to_string()) {
+                    return Err(self.error(ParseErrorKind::UnexpectedEnd, start));
+                }
+                value
+            }
+            Some(_) => self.take_while(|c| !c.is_whitespace() && c != '>'),
+            None => return Err(self.error(ParseErrorKind::UnexpectedEnd, start)),
+        };
+        self.decode(raw, start)
+    }
+
+    /// Decodes the entities in `text`, which starts at byte `start` of the HTML.
+    /// An `&` that doesn't start an entity stays as it is, like in the browser.
+    fn decode(&self, text: &str, start: usize) -> Result<String, ParseError> {
+        let mut decoded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(amp) = rest.find('&') {
+            decoded.push_str(&rest[..amp]);
+            let after = &rest[amp + 1..];
+            let name_len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '#'))
+                .unwrap_or(after.len());
+
+            if name_len > 0 && after[name_len..].starts_with(';') {
+                let entity = &rest[amp..amp + name_len + 2];
+                match decode_html(entity) {
+                    Ok(c) => decoded.push_str(&c),
+                    Err(_) => {
+                        let position = start + (text.len() - rest.len()) + amp;
+                        return Err(self.error(
+                            ParseErrorKind::InvalidEntity(String::from(entity)),
+                            position,
+                        ));
+                    }
+                }
+                rest = &rest[amp + name_len + 2..];
+            } else {
+                decoded.push('&');
+                rest = after;
+            }
+        }
+        decoded.push_str(rest);
+        Ok(decoded)
+    }
+}
+
+/// Whether `html` starts with an opening tag, `<` followed by a letter
+fn starts_opening_tag(html: &str) -> bool {
+    let mut chars = html.chars();
+    chars.next() == Some('<') && matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+}
+
+/// Whether `html` starts with a tag, a comment or a doctype
+fn starts_markup(html: &str) -> bool {
+    starts_opening_tag(html)
+        || ["</", "<!", "<?"]
+            .iter()
+            .any(|start| html.starts_with(start))
+}
@@ -1,205 +1,482 @@
-use super::node::{Attribute, Element, VirtualDomNode, VirtualElementNode, VirtualTextNode};
-use web_sys::console;
+use super::component::Lifecycle;
+use super::diff::{diff, Patch};
+use super::dom::{DomBackend, WebDom};
+use super::events::{Handler, SyntheticEvent};
+use super::node::{
+    Attribute, Element, Listener, Node, NodeKey, VirtualDomNode, VirtualElementNode,
+    VirtualRawHtml, VirtualTextNode,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 pub fn h(node_type: &str, children: Vec<VirtualDomNode>, attr: Vec<Attribute>) -> VirtualDomNode {
     VirtualDomNode::ElementNode(VirtualElementNode {
+        key: NodeKey::NONE,
         node_type: String::from(node_type),
         children,
         attributes: attr,
+        listeners: vec![],
+        handlers: vec![],
+        hooks: None,
     })
 }
 
+/// Gives an element node a key, so it keeps its real DOM node when its siblings are
+/// reordered, inserted or removed. Keys must be unique among siblings.
+pub fn keyed(key: u32, node: VirtualDomNode) -> VirtualDomNode {
+    match node {
+        VirtualDomNode::ElementNode(mut vnode) => {
+            vnode.key = NodeKey::new(key);
+            VirtualDomNode::ElementNode(vnode)
+        }
+        node => node,
+    }
+}
+
+/// Adds event listeners to an element node, they are attached when the element
+/// is created and swapped or removed when it is diffed.
+pub fn with_listeners(listeners: Vec<Listener>, node: VirtualDomNode) -> VirtualDomNode {
+    match node {
+        VirtualDomNode::ElementNode(mut vnode) => {
+            vnode.listeners.extend(listeners);
+            VirtualDomNode::ElementNode(vnode)
+        }
+        node => node,
+    }
+}
+
+pub fn listener<F>(event: &str, callback: F) -> Listener
+where
+    F: 'static + Fn(web_sys::Event),
+{
+    Listener {
+        event: String::from(event),
+        callback: Rc::new(callback),
+        attached: RefCell::new(None),
+    }
+}
+
+/// Adds handlers to an element node, they are run by the `VirtualDom` when an
+/// event reaches the element, without listening on the element itself.
+pub fn with_handlers(handlers: Vec<Handler>, node: VirtualDomNode) -> VirtualDomNode {
+    match node {
+        VirtualDomNode::ElementNode(mut vnode) => {
+            vnode.handlers.extend(handlers);
+            VirtualDomNode::ElementNode(vnode)
+        }
+        node => node,
+    }
+}
+
+/// A handler for the bubble phase, it runs after the handlers of the descendants
+pub fn handler<F>(event: &str, callback: F) -> Handler
+where
+    F: 'static + Fn(&SyntheticEvent),
+{
+    Handler {
+        event: String::from(event),
+        capture: false,
+        callback: Rc::new(callback),
+    }
+}
+
+/// A handler for the capture phase, it runs before the handlers of the descendants
+pub fn capture_handler<F>(event: &str, callback: F) -> Handler
+where
+    F: 'static + Fn(&SyntheticEvent),
+{
+    Handler {
+        event: String::from(event),
+        capture: true,
+        callback: Rc::new(callback),
+    }
+}
+
+/// An attribute, checked when it is rendered so that a value from a user
+/// can't run scripts, see `Attribute::rendered_value`
 pub fn attr(attribute: &str, value: &str) -> Attribute {
     Attribute {
         name: String::from(attribute),
         value: String::from(value),
+        trusted: false,
+    }
+}
+
+/// An attribute rendered exactly as given, even a string event handler or a
+/// `javascript:` URL. Only use it for values that can never come from a user.
+pub fn dangerous_attr(attribute: &str, value: &str) -> Attribute {
+    Attribute {
+        name: String::from(attribute),
+        value: String::from(value),
+        trusted: true,
     }
 }
 
+pub fn fragment(children: Vec<VirtualDomNode>) -> VirtualDomNode {
+    VirtualDomNode::Fragment(children)
+}
+
+/// A `node_type` element holding `html` as its `innerHTML`, for trusted markup
+/// such as highlighted code or SVG made on the server. A new string replaces
+/// everything inside the element.
+///
+/// Nothing in `html` is escaped or checked, here or by `ssr`, so it must never
+/// hold anything from a user that didn't go through `sanitize` first.
+pub fn dangerously_set_inner_html(node_type: &str, html: &str) -> VirtualDomNode {
+    VirtualDomNode::RawHtml(VirtualRawHtml {
+        node_type: String::from(node_type),
+        html: String::from(html),
+    })
+}
+
 pub fn t(text: &str) -> VirtualDomNode {
     VirtualDomNode::TextNode(VirtualTextNode {
         text: String::from(text),
     })
 }
 
-pub fn create_element_from_node(parent: &mut Element, node: &VirtualDomNode) -> Option<Element> {
-    let window = web_sys::window().expect("no global `window` exists");
-    let document = window.document().expect("Document");
+/// Wraps a `{expr}` child of `html!`, so anything that converts into a
+/// `VirtualDomNode` can be used as a child, as well as iterators of those
+#[doc(hidden)]
+pub struct Child<T>(pub T);
+
+impl<T: Into<VirtualDomNode>> Child<T> {
+    /// Strings, nodes and options become a single node
+    pub fn into_node(self) -> VirtualDomNode {
+        self.0.into()
+    }
+}
+
+/// Turns an iterator child of `html!` into a fragment, this is only picked when
+/// the child doesn't convert into a `VirtualDomNode` by itself
+#[doc(hidden)]
+pub trait IntoChildNode {
+    fn into_node(self) -> VirtualDomNode;
+}
+
+impl<I> IntoChildNode for Child<I>
+where
+    I: IntoIterator,
+    I::Item: Into<VirtualDomNode>,
+{
+    fn into_node(self) -> VirtualDomNode {
+        VirtualDomNode::Fragment(self.0.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Creates the real DOM for `node`, appends it to `parent` and returns it.
+/// Fragments append all of their children and return `None`.
+pub fn create_element_from_node(parent: &mut Element, node: &VirtualDomNode) -> Option<Node> {
+    let parent: web_sys::Node = parent.el.clone()?.into();
+    create_node_with(&WebDom::new(), &parent, node).map(Node::from)
+}
 
+/// Creates the DOM for `node` with any backend, appends it to `parent` and
+/// returns it. Fragments append all of their children and return `None`.
+pub fn create_node_with<B: DomBackend>(
+    dom: &B,
+    parent: &B::Node,
+    node: &VirtualDomNode,
+) -> Option<B::Node> {
+    match node {
+        VirtualDomNode::Fragment(children) => {
+            // there is no node for the fragment itself, its children go straight into the parent
+            for c in children.iter() {
+                create_node_with(dom, parent, c);
+            }
+            None
+        }
+        _ => {
+            let child = build_node(dom, node)?;
+            dom.append_child(parent, &child);
+            Some(child)
+        }
+    }
+}
 
+/// Creates the DOM for `node` without putting it anywhere, a fragment has no node of its own
+pub(crate) fn build_node<B: DomBackend>(dom: &B, node: &VirtualDomNode) -> Option<B::Node> {
     match node {
         VirtualDomNode::ElementNode(vnode) => {
-            let mut el: Element = document
-                .create_element(&vnode.node_type)
-                .ok()
-                .unwrap()
-                .into();
+            let el = dom.create_element(&vnode.node_type);
 
             for attr in vnode.attributes.iter() {
-                let _res = el.set_attribute(&attr.name, &attr.value);
-                console::log_1(&"Setting attribute".into());
+                if let Some(value) = attr.rendered_value() {
+                    dom.set_attribute(&el, &attr.name, value);
+                }
+            }
+
+            for listener in vnode.listeners.iter() {
+                dom.add_listener(&el, listener);
             }
 
+            // children append themselves, so text and elements keep their order
             for c in vnode.children.iter() {
-                let child_element: Option<Element> = create_element_from_node(&mut el, c);
-                match child_element {
-                    Some(mut x) => el.append_child(&mut x),
-                    None => console::log_1(&"Emprty stuff".into()),
-                } // vrati element
+                create_node_with(dom, &el, c);
             }
 
-            parent.append_child(&mut el);
             Some(el)
         }
-        VirtualDomNode::TextNode(text_node) => {
-            parent.set_text_content(&text_node.text);
-            None
-
-        }
-        VirtualDomNode::Empty => {
-            let mut el: Element = document.create_element("div").ok().unwrap().into();
-            el.set_text_content("empty");
+        VirtualDomNode::TextNode(text_node) => Some(dom.create_text_node(&text_node.text)),
+        VirtualDomNode::RawHtml(raw) => {
+            let el = dom.create_element(&raw.node_type);
+            dom.set_inner_html(&el, &raw.html);
             Some(el)
         }
+        VirtualDomNode::Fragment(_) => None,
+        // an invisible placeholder that keeps the sibling position of the empty node
+        VirtualDomNode::Empty => Some(dom.create_placeholder()),
     }
-
 }
 
+/// Compares `new_node` against `old_node`, both living at `child_index` of `parent`,
+/// and applies only the resulting patches to the real DOM
 pub fn update_element(
     parent: &mut Element,
-    _child_index: usize,
+    child_index: usize,
     new_node: &VirtualDomNode,
-    _old_node: &VirtualDomNode,
+    old_node: &VirtualDomNode,
 ) {
-    let _child = create_element_from_node(parent, &new_node);
+    let patches = diff(child_index, new_node, old_node);
+    apply_patches(parent, &patches);
 }
 
-/*
-use crate::{Node, RenderContext};
-use std::any::Any;
-use std::rc::Rc;
-use wasm_bindgen::UnwrapThrowExt;
-
-/// A trait for any component that can be rendered to HTML.
-///
-/// Takes a shared reference to `self` and generates the virtual DOM that
-/// represents its rendered HTML.
-///
-/// ## `Bump` Allocation
-///
-/// `Render` implementations can use the `Bump` inside the provided
-/// `RenderContext` for very fast allocation for anything that needs to be
-/// temporarily allocated during rendering.
-///
-/// ## Example
-///
-/// ```no_run
-/// use dodrio::{Node, Render, RenderContext};
-///
-/// pub struct MyComponent;
+/// Applies a list of patches, in order, to the real DOM below `root`.
 ///
-/// impl Render for MyComponent {
-///     fn render<'a>(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
-///         use dodrio::builder::*;
-///
-///         p(&cx)
-///             .children([
-///                 text("This is "),
-///                 strong(&cx).children([text("my component")]).finish(),
-///                 text(" rendered!"),
-///             ])
-///             .finish()
-///     }
-/// }
-/// ```
-pub trait Render {
-    /// Render `self` as a virtual DOM. Use the given context's `Bump` for
-    /// temporary allocations.
-    fn render<'a>(&self, cx: &mut RenderContext<'a>) -> Node<'a>;
-}
-
-impl<'r, R> Render for &'r R
-where
-    R: Render,
-{
-    fn render<'a>(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
-        (**self).render(cx)
+/// `before_unmount` hooks run right before their element is removed, the
+/// `mounted` and `updated` hooks once every patch is applied.
+pub fn apply_patches(root: &mut Element, patches: &[Patch]) {
+    if let Some(el) = root.el.clone() {
+        apply_patches_with(&WebDom::new(), &el.into(), patches);
     }
 }
 
-impl<R> Render for Rc<R>
-where
-    R: Render,
-{
-    fn render<'a>(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
-        (**self).render(cx)
+/// Applies a list of patches, in order, to the DOM of any backend below `root`
+pub fn apply_patches_with<B: DomBackend>(dom: &B, root: &B::Node, patches: &[Patch]) {
+    let mut pending = Vec::new();
+
+    for patch in patches.iter() {
+        match patch {
+            Patch::Create {
+                parent,
+                index,
+                node,
+            } => {
+                if let Some(parent) = node_at(dom, root, parent) {
+                    if let Some(child) = insert_node(dom, &parent, Some(*index), node) {
+                        queue_mounted(dom, &child, node, &mut pending);
+                    }
+                }
+            }
+            Patch::AppendChild { parent, node } => {
+                if let Some(parent) = node_at(dom, root, parent) {
+                    if let Some(child) = insert_node(dom, &parent, None, node) {
+                        queue_mounted(dom, &child, node, &mut pending);
+                    }
+                }
+            }
+            Patch::Move { parent, from, to } => {
+                if let Some(parent) = node_at(dom, root, parent) {
+                    if let Some(child) = dom.child(&parent, *from) {
+                        // `to` is counted without the moved child itself
+                        let reference_index = if to < from { *to } else { *to + 1 };
+                        let reference = dom.child(&parent, reference_index);
+                        dom.insert_before(&parent, &child, reference.as_ref());
+                    }
+                }
+            }
+            Patch::Remove { path, node } => {
+                if let Some((parent, child)) = parent_and_node_at(dom, root, path) {
+                    call_before_unmount(dom, &child, node);
+                    dom.remove_child(&parent, &child);
+                }
+            }
+            Patch::Replace { path, old, node } => {
+                if let Some((parent, old_child)) = parent_and_node_at(dom, root, path) {
+                    call_before_unmount(dom, &old_child, old);
+                    if let Some(new_child) = build_node(dom, node) {
+                        dom.replace_child(&parent, &new_child, &old_child);
+                        queue_mounted(dom, &new_child, node, &mut pending);
+                    }
+                }
+            }
+            Patch::SetText { path, text } => {
+                // update the text node in place instead of creating a new one
+                if let Some(text_node) = node_at(dom, root, path) {
+                    dom.set_text(&text_node, text);
+                }
+            }
+            Patch::SetInnerHtml { path, html } => {
+                if let Some(el) = node_at(dom, root, path) {
+                    dom.set_inner_html(&el, html);
+                }
+            }
+            Patch::SetAttribute { path, name, value } => {
+                if let Some(el) = node_at(dom, root, path) {
+                    dom.set_attribute(&el, name, value);
+                }
+            }
+            Patch::AddListener { path, listener } => {
+                if let Some(el) = node_at(dom, root, path) {
+                    dom.add_listener(&el, listener);
+                }
+            }
+            Patch::UpdateListener { path, old, new } => {
+                // the JS closure stays on the element, only the callback it calls changes
+                if !old.transfer_to(new) {
+                    if let Some(el) = node_at(dom, root, path) {
+                        dom.add_listener(&el, new);
+                    }
+                }
+            }
+            Patch::RemoveListener { listener, .. } => listener.detach(),
+            Patch::RemoveAttribute { path, name } => {
+                if let Some(el) = node_at(dom, root, path) {
+                    dom.remove_attribute(&el, name);
+                }
+            }
+            Patch::Updated { path, node } => {
+                let el = node_at(dom, root, path).and_then(|el| dom.element(&el));
+                if let (VirtualDomNode::ElementNode(vnode), Some(el)) = (node, el) {
+                    if let Some(hooks) = vnode.hooks.as_ref() {
+                        pending.push(PendingHook::Updated(hooks.clone(), el));
+                    }
+                }
+            }
+        }
     }
+
+    call_pending_hooks(pending);
 }
 
-/// A `RootRender` is a render component that can be the root rendering component
-/// mounted to a virtual DOM.
-///
-/// In addition to rendering, it must also be `'static` so that it can be owned
-/// by the virtual DOM and `Any` so that it can be downcast to its concrete type
-/// by event listener callbacks.
-///
-/// You do not need to implement this trait by hand: there is a blanket
-/// implementation for all `Render` types that fulfill the `RootRender`
-/// requirements.
-pub trait RootRender: Any + Render {
-    /// Get this `&RootRender` trait object as an `&Any` trait object reference.
-    fn as_any(&self) -> &Any;
+/// Calls the `mounted` hooks in `node`, the tree that was just created as the
+/// first child of `root`
+pub(crate) fn call_mounted<B: DomBackend>(dom: &B, root: &B::Node, node: &VirtualDomNode) {
+    let mut pending = Vec::new();
+    for (child, vnode) in dom_children(dom, root, std::slice::from_ref(node)) {
+        queue_mounted(dom, &child, vnode, &mut pending);
+    }
+    call_pending_hooks(pending);
+}
+
+/// Removes the DOM of a tree rendered at the start of `root`, calling its
+/// `before_unmount` hooks first
+pub(crate) fn remove_node_with<B: DomBackend>(dom: &B, root: &B::Node, node: &VirtualDomNode) {
+    for (child, vnode) in dom_children(dom, root, std::slice::from_ref(node)) {
+        call_before_unmount(dom, &child, vnode);
+        dom.remove_child(root, &child);
+    }
+}
 
-    /// Get this `&mut RootRender` trait object as an `&mut Any` trait object
-    /// reference.
-    fn as_any_mut(&mut self) -> &mut Any;
+/// A `mounted` or `updated` hook waiting for the whole tree to be patched
+enum PendingHook {
+    Mounted(Rc<dyn Lifecycle>, Element),
+    Updated(Rc<dyn Lifecycle>, Element),
 }
 
-impl<T> RootRender for T
-where
-    T: Any + Render,
-{
-    fn as_any(&self) -> &Any {
-        self
-    }
-
-    fn as_any_mut(&mut self) -> &mut Any {
-        self
-    }
-}
-
-impl dyn RootRender {
-    /// Downcast this shared `&dyn RootRender` trait object reference to its
-    /// underlying concrete type.
-    ///
-    /// # Panics
-    ///
-    /// Panics if this virtual DOM's root rendering component is not an `R`
-    /// instance.
-    pub fn unwrap_ref<R>(&self) -> &R
-    where
-        R: RootRender,
-    {
-        self.as_any()
-            .downcast_ref::<R>()
-            .expect_throw("bad `RootRender::unwrap_ref` call")
-    }
-
-    /// Downcast this exclusive `&mut dyn RootRender` trait object reference to
-    /// its underlying concrete type.
-    ///
-    /// # Panics
-    ///
-    /// Panics if this virtual DOM's root rendering component is not an `R`
-    /// instance.
-    pub fn unwrap_mut<R>(&mut self) -> &mut R
-    where
-        R: RootRender,
-    {
-        self.as_any_mut()
-            .downcast_mut::<R>()
-            .expect_throw("bad `RootRender::unwrap_ref` call")
-    }
-}
-
- */
+fn call_pending_hooks(pending: Vec<PendingHook>) {
+    for hook in pending {
+        match hook {
+            PendingHook::Mounted(hooks, el) => hooks.mounted(el),
+            PendingHook::Updated(hooks, el) => hooks.updated(el),
+        }
+    }
+}
+
+/// Queues the `mounted` hooks of `node` and everything below it, children
+/// first. `node_dom` is the DOM node that was just created for `node`.
+fn queue_mounted<B: DomBackend>(
+    dom: &B,
+    node_dom: &B::Node,
+    node: &VirtualDomNode,
+    pending: &mut Vec<PendingHook>,
+) {
+    if let VirtualDomNode::ElementNode(vnode) = node {
+        for (child, vchild) in dom_children(dom, node_dom, &vnode.children) {
+            queue_mounted(dom, &child, vchild, pending);
+        }
+        if let (Some(hooks), Some(el)) = (vnode.hooks.as_ref(), dom.element(node_dom)) {
+            pending.push(PendingHook::Mounted(hooks.clone(), el));
+        }
+    }
+}
+
+/// Calls the `before_unmount` hooks of `node` and everything below it,
+/// parents first. `node_dom` is the DOM node of `node`, still in the DOM.
+fn call_before_unmount<B: DomBackend>(dom: &B, node_dom: &B::Node, node: &VirtualDomNode) {
+    if let VirtualDomNode::ElementNode(vnode) = node {
+        if let (Some(hooks), Some(el)) = (vnode.hooks.as_ref(), dom.element(node_dom)) {
+            hooks.before_unmount(el);
+        }
+        for (child, vchild) in dom_children(dom, node_dom, &vnode.children) {
+            call_before_unmount(dom, &child, vchild);
+        }
+    }
+}
+
+/// Pairs `children`, with their fragments flattened, with the DOM children of `node_dom`
+fn dom_children<'a, B: DomBackend>(
+    dom: &B,
+    node_dom: &B::Node,
+    children: &'a [VirtualDomNode],
+) -> Vec<(B::Node, &'a VirtualDomNode)> {
+    flatten_children(children)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, child)| Some((dom.child(node_dom, i)?, child)))
+        .collect()
+}
+
+/// `children` with their fragments replaced by what is in them, one entry per DOM node
+pub(crate) fn flatten_children(children: &[VirtualDomNode]) -> Vec<&VirtualDomNode> {
+    fn flatten<'a>(node: &'a VirtualDomNode, flat: &mut Vec<&'a VirtualDomNode>) {
+        match node {
+            VirtualDomNode::Fragment(children) => {
+                for child in children.iter() {
+                    flatten(child, flat);
+                }
+            }
+            _ => flat.push(node),
+        }
+    }
+
+    let mut flat = Vec::new();
+    for child in children.iter() {
+        flatten(child, &mut flat);
+    }
+    flat
+}
+
+/// Walks `path` down from `root` and returns the DOM node it points at
+fn node_at<B: DomBackend>(dom: &B, root: &B::Node, path: &[usize]) -> Option<B::Node> {
+    let mut node = root.clone();
+    for index in path.iter() {
+        node = dom.child(&node, *index)?;
+    }
+    Some(node)
+}
+
+/// The DOM node `path` points at, along with its parent
+fn parent_and_node_at<B: DomBackend>(
+    dom: &B,
+    root: &B::Node,
+    path: &[usize],
+) -> Option<(B::Node, B::Node)> {
+    let (index, parent_path) = path.split_last()?;
+    let parent = node_at(dom, root, parent_path)?;
+    let node = dom.child(&parent, *index)?;
+    Some((parent, node))
+}
+
+/// Creates the DOM for `node` and puts it at `index` of `parent`, or at the end when there is no index
+fn insert_node<B: DomBackend>(
+    dom: &B,
+    parent: &B::Node,
+    index: Option<usize>,
+    node: &VirtualDomNode,
+) -> Option<B::Node> {
+    let child = build_node(dom, node)?;
+    let reference = index.and_then(|i| dom.child(parent, i));
+    dom.insert_before(parent, &child, reference.as_ref());
+    Some(child)
+}
@@ -0,0 +1,319 @@
+use super::node::{
+    has_url_scheme, is_event_handler_attribute, is_url_attribute, Attribute, VirtualDomNode,
+    VirtualElementNode,
+};
+use super::parse::{parse_html, ParseError, MAX_DEPTH};
+use super::render::h;
+use std::collections::HashSet;
+
+/// Turns untrusted HTML, such as comments or rich text from users, into nodes
+/// that only use the tags and attributes it allows.
+///
+/// An element that is not allowed is dropped but its children are kept, except
+/// for `<script>` and `<style>` which go with all of their content. Attributes
+/// that are not allowed are dropped, and so are `on*` event handlers and
+/// `javascript:` and `data:` URLs even when their attribute is allowed. The
+/// attributes that are kept are untrusted, even if they were made with
+/// `dangerous_attr`, so they still get checked when rendered. Nothing is nested
+/// deeper than `parse::MAX_DEPTH`, so hostile markup can't overflow the stack.
+///
+/// ```ignore
+/// let comment = Sanitizer::new()
+///     .allow_tags(&["figure", "figcaption"])
+///     .sanitize(&untrusted)?;
+/// for removal in comment.removed.iter() {
+///     log::warn!("removed from a comment: {:?}", removal);
+/// }
+/// vdom.render(&mut root, fragment(comment.nodes));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sanitizer {
+    tags: HashSet<String>,
+    attributes: HashSet<String>,
+}
+
+/// The nodes left after sanitizing, and what was taken out of them
+#[derive(Debug)]
+pub struct Sanitized {
+    pub nodes: Vec<VirtualDomNode>,
+    pub removed: Vec<Removal>,
+}
+
+/// Something the sanitizer took out, in the order it was found
+#[derive(Clone, Debug, PartialEq)]
+pub enum Removal {
+    /// An element whose tag is not allowed, its children were kept in its place
+    Tag(String),
+    /// A `<script>` or `<style>` element, or raw HTML that can't be parsed,
+    /// removed with everything in it
+    Element(String),
+    /// An attribute that is not allowed or is an event handler
+    Attribute { tag: String, name: String },
+    /// An element nested deeper than `parse::MAX_DEPTH`, removed with
+    /// everything in it
+    TooDeep(String),
+    /// An attribute holding a `javascript:` or `data:` URL
+    Url {
+        tag: String,
+        name: String,
+        value: String,
+    },
+}
+
+/// Tags of simple formatted text, what `Sanitizer::new` allows
+const RICH_TEXT_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "del",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+
+/// Attributes `Sanitizer::new` allows on any of its tags
+const RICH_TEXT_ATTRIBUTES: &[&str] = &[
+    "alt", "cite", "colspan", "height", "href", "rowspan", "src", "title", "width",
+];
+
+impl Sanitizer {
+    /// A sanitizer allowing the tags and attributes of simple formatted text,
+    /// like paragraphs, lists, links, images and tables
+    pub fn new() -> Sanitizer {
+        Sanitizer::empty()
+            .allow_tags(RICH_TEXT_TAGS)
+            .allow_attributes(RICH_TEXT_ATTRIBUTES)
+    }
+
+    /// A sanitizer allowing no tags and no attributes, so only text is left
+    pub fn empty() -> Sanitizer {
+        Sanitizer {
+            tags: HashSet::new(),
+            attributes: HashSet::new(),
+        }
+    }
+
+    pub fn allow_tags(mut self, tags: &[&str]) -> Sanitizer {
+        self.tags.extend(tags.iter().map(|tag| tag.to_lowercase()));
+        self
+    }
+
+    pub fn deny_tags(mut self, tags: &[&str]) -> Sanitizer {
+        for tag in tags.iter() {
+            self.tags.remove(&tag.to_lowercase());
+        }
+        self
+    }
+
+    /// Allows attributes on every allowed tag. Event handlers stay removed.
+    pub fn allow_attributes(mut self, attributes: &[&str]) -> Sanitizer {
+        self.attributes
+            .extend(attributes.iter().map(|name| name.to_lowercase()));
+        self
+    }
+
+    pub fn deny_attributes(mut self, attributes: &[&str]) -> Sanitizer {
+        for name in attributes.iter() {
+            self.attributes.remove(&name.to_lowercase());
+        }
+        self
+    }
+
+    /// Parses `html` and sanitizes the nodes in it
+    pub fn sanitize(&self, html: &str) -> Result<Sanitized, ParseError> {
+        Ok(self.sanitize_nodes(parse_html(html)?))
+    }
+
+    /// Sanitizes nodes that came from somewhere else than `sanitize`.
+    /// The elements that are left keep their listeners and handlers.
+    pub fn sanitize_nodes(&self, nodes: Vec<VirtualDomNode>) -> Sanitized {
+        let mut removed = Vec::new();
+        // the tree is walked with a stack rather than recursion, so however
+        // deep the nodes are they can't overflow the call stack
+        let mut steps: Vec<Step> = nodes
+            .into_iter()
+            .rev()
+            .map(|node| Step::Visit(node, 1))
+            .collect();
+        // the elements being sanitized, each with the children it has so far
+        let mut open: Vec<(Option<VirtualElementNode>, Vec<VirtualDomNode>)> = vec![(None, vec![])];
+
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Visit(node, depth) => {
+                    if let Some(node) = self.sanitize_node(node, depth, &mut steps, &mut removed) {
+                        match node {
+                            VirtualDomNode::ElementNode(vnode) => open.push((Some(vnode), vec![])),
+                            node => open.last_mut().unwrap().1.push(node),
+                        }
+                    }
+                }
+                Step::Close => {
+                    let (vnode, children) = open.pop().unwrap();
+                    let mut vnode = vnode.unwrap();
+                    vnode.children = children;
+                    open.last_mut()
+                        .unwrap()
+                        .1
+                        .push(VirtualDomNode::ElementNode(vnode));
+                }
+            }
+        }
+
+        Sanitized {
+            nodes: open.pop().unwrap().1,
+            removed,
+        }
+    }
+
+    /// Sanitizes `node`, `depth` counts it and the elements and fragments it is
+    /// in. Returns the node when it is kept, its children are pushed onto
+    /// `steps` to be sanitized next, followed by a `Step::Close` for a kept element.
+    fn sanitize_node(
+        &self,
+        node: VirtualDomNode,
+        depth: usize,
+        steps: &mut Vec<Step>,
+        removed: &mut Vec<Removal>,
+    ) -> Option<VirtualDomNode> {
+        let visit_children = |steps: &mut Vec<Step>, children: Vec<VirtualDomNode>| {
+            steps.extend(
+                children
+                    .into_iter()
+                    .rev()
+                    .map(|child| Step::Visit(child, depth + 1)),
+            )
+        };
+
+        let mut vnode = match node {
+            VirtualDomNode::ElementNode(vnode) => vnode,
+            VirtualDomNode::Fragment(children) => {
+                visit_children(steps, children);
+                return None;
+            }
+            // raw HTML is sanitized like any other, as the nodes it parses into
+            VirtualDomNode::RawHtml(raw) => {
+                match parse_html(&raw.html) {
+                    Ok(children) => {
+                        steps.push(Step::Visit(h(&raw.node_type, children, vec![]), depth))
+                    }
+                    Err(_) => removed.push(Removal::Element(raw.node_type)),
+                }
+                return None;
+            }
+            node => return Some(node),
+        };
+
+        let tag = vnode.node_type.to_lowercase();
+        let children = std::mem::take(&mut vnode.children);
+        if depth > MAX_DEPTH {
+            removed.push(Removal::TooDeep(tag));
+            discard_all(children);
+            return None;
+        }
+        if tag == "script" || tag == "style" {
+            removed.push(Removal::Element(tag));
+            discard_all(children);
+            return None;
+        }
+
+        if !self.tags.contains(&tag) {
+            // the element goes, what is in it takes its place
+            removed.push(Removal::Tag(tag));
+            visit_children(steps, children);
+            return None;
+        }
+
+        let attributes = std::mem::take(&mut vnode.attributes);
+        vnode.attributes = attributes
+            .into_iter()
+            .filter(|attribute| self.keep_attribute(&tag, attribute, removed))
+            .map(|mut attribute| {
+                attribute.trusted = false;
+                attribute
+            })
+            .collect();
+        steps.push(Step::Close);
+        visit_children(steps, children);
+        Some(VirtualDomNode::ElementNode(vnode))
+    }
+
+    fn keep_attribute(&self, tag: &str, attribute: &Attribute, removed: &mut Vec<Removal>) -> bool {
+        let name = attribute.name.to_lowercase();
+        if is_event_handler_attribute(&name) || !self.attributes.contains(&name) {
+            removed.push(Removal::Attribute {
+                tag: String::from(tag),
+                name,
+            });
+            return false;
+        }
+        if is_url_attribute(&name)
+            && (has_url_scheme(&attribute.value, "javascript:")
+                || has_url_scheme(&attribute.value, "data:"))
+        {
+            removed.push(Removal::Url {
+                tag: String::from(tag),
+                name,
+                value: attribute.value.clone(),
+            });
+            return false;
+        }
+        true
+    }
+}
+
+impl Default for Sanitizer {
+    fn default() -> Sanitizer {
+        Sanitizer::new()
+    }
+}
+
+/// What is left to do while walking the nodes to sanitize
+enum Step {
+    /// Sanitize a node at a depth
+    Visit(VirtualDomNode, usize),
+    /// Every child of the innermost kept element is sanitized, so it is done
+    Close,
+}
+
+/// Drops removed nodes one level at a time, since dropping a deep tree
+/// recurses once per level
+fn discard_all(mut nodes: Vec<VirtualDomNode>) {
+    while let Some(node) = nodes.pop() {
+        match node {
+            VirtualDomNode::ElementNode(mut vnode) => nodes.append(&mut vnode.children),
+            VirtualDomNode::Fragment(mut children) => nodes.append(&mut children),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Batches renders so that however many times a render is scheduled, it runs
+/// at most once per animation frame.
+///
+/// Outside of a browser there are no animation frames, so a scheduled render
+/// runs right away instead.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Rc<SchedulerInner>,
+}
+
+struct SchedulerInner {
+    render: Box<dyn Fn()>,
+    dirty: Cell<bool>,
+    rendering: Cell<bool>,
+    frame: Cell<Option<i32>>,
+    // created on the first frame request and reused for every frame after it
+    frame_callback: RefCell<Option<Closure<dyn FnMut()>>>,
+}
+
+impl Scheduler {
+    /// A scheduler calling `render` whenever a scheduled render is due
+    pub fn new<F>(render: F) -> Scheduler
+    where
+        F: 'static + Fn(),
+    {
+        Scheduler {
+            inner: Rc::new(SchedulerInner {
+                render: Box::new(render),
+                dirty: Cell::new(false),
+                rendering: Cell::new(false),
+                frame: Cell::new(None),
+                frame_callback: RefCell::new(None),
+            }),
+        }
+    }
+
+    /// Marks the tree dirty, it gets rendered on the next animation frame
+    pub fn schedule_render(&self) {
+        self.inner.dirty.set(true);
+        // the render that is running right now picks it up before returning
+        if self.inner.rendering.get() {
+            return;
+        }
+        if !SchedulerInner::request_frame(&self.inner) {
+            self.flush();
+        }
+    }
+
+    /// Whether a render is scheduled but didn't run yet
+    pub fn is_dirty(&self) -> bool {
+        self.inner.dirty.get()
+    }
+
+    /// Runs the scheduled render now instead of waiting for the next frame,
+    /// does nothing when no render is scheduled
+    pub fn flush(&self) {
+        self.inner.cancel_frame();
+        if self.inner.rendering.replace(true) {
+            return;
+        }
+        while self.inner.dirty.replace(false) {
+            (self.inner.render)();
+        }
+        self.inner.rendering.set(false);
+    }
+}
+
+impl SchedulerInner {
+    /// Asks the browser for an animation frame, returns `false` when there is no browser
+    fn request_frame(inner: &Rc<SchedulerInner>) -> bool {
+        if inner.frame.get().is_some() {
+            return true;
+        }
+        // web_sys panics when it is called outside of wasm
+        if !cfg!(target_arch = "wasm32") {
+            return false;
+        }
+        let window = match web_sys::window() {
+            Some(window) => window,
+            None => return false,
+        };
+
+        let mut frame_callback = inner.frame_callback.borrow_mut();
+        let callback = frame_callback.get_or_insert_with(|| {
+            let weak: Weak<SchedulerInner> = Rc::downgrade(inner);
+            Closure::wrap(Box::new(move || {
+                if let Some(inner) = weak.upgrade() {
+                    inner.frame.set(None);
+                    Scheduler { inner }.flush();
+                }
+            }) as Box<dyn FnMut()>)
+        });
+        match window.request_animation_frame(callback.as_ref().unchecked_ref()) {
+            Ok(frame) => {
+                inner.frame.set(Some(frame));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn cancel_frame(&self) {
+        if let Some(frame) = self.frame.take() {
+            if let Some(window) = web_sys::window() {
+                let _res = window.cancel_animation_frame(frame);
+            }
+        }
+    }
+}
+
+impl Drop for SchedulerInner {
+    // the browser must not call the frame callback once it is freed
+    fn drop(&mut self) {
+        self.cancel_frame();
+    }
+}
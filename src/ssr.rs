@@ -0,0 +1,158 @@
+use super::node::{
+    is_boolean_attribute, is_raw_text_element, is_void_element, Attribute, VirtualDomNode,
+};
+use htmlescape::encode_minimal_w;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Renders `node` as an HTML string, so pages can be rendered on the server
+/// from the same view code the browser uses.
+///
+/// Text and attribute values are escaped, except for the text in `<script>` and
+/// `<style>`, where only a closing tag is broken up. Void elements get no closing tag and
+/// boolean attributes are written without a value. Attributes go through the
+/// same checks as in the browser, see `Attribute::rendered_value`. Listeners and handlers only
+/// exist in the browser, so they are left out. An empty node is written as the
+/// comment the browser renders for it.
+pub fn render_to_string(node: &VirtualDomNode) -> String {
+    let mut html = String::new();
+    HtmlWriter::new()
+        .write_to_fmt(node, &mut html)
+        .expect("writing to a String can't fail");
+    html
+}
+
+impl VirtualDomNode {
+    /// The HTML for this node, see `render_to_string`
+    pub fn to_html(&self) -> String {
+        render_to_string(self)
+    }
+}
+
+/// Streams the HTML of a tree straight into a sink, tag by tag and text by
+/// text, so a large page is never held in memory as a whole. The output is
+/// the same as `render_to_string`, byte for byte.
+///
+/// ```ignore
+/// let stdout = std::io::stdout();
+/// HtmlWriter::new().doctype(true).write_to(&page, &mut stdout.lock())?;
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HtmlWriter {
+    doctype: bool,
+}
+
+impl HtmlWriter {
+    pub fn new() -> HtmlWriter {
+        HtmlWriter { doctype: false }
+    }
+
+    /// Whether to start with `<!DOCTYPE html>`, for a whole page
+    pub fn doctype(mut self, doctype: bool) -> HtmlWriter {
+        self.doctype = doctype;
+        self
+    }
+
+    /// Writes the HTML of `node` into an `io::Write` sink such as a file or a socket
+    pub fn write_to<W: Write>(&self, node: &VirtualDomNode, out: &mut W) -> io::Result<()> {
+        if self.doctype {
+            out.write_all(b"<!DOCTYPE html>")?;
+        }
+        write_node(node, out)
+    }
+
+    /// Writes the HTML of `node` into a `fmt::Write` sink such as a `String`
+    pub fn write_to_fmt<W: fmt::Write>(&self, node: &VirtualDomNode, out: &mut W) -> fmt::Result {
+        self.write_to(node, &mut FmtSink(out))
+            .map_err(|_| fmt::Error)
+    }
+}
+
+/// Lets the `io::Write` serializer write into a `fmt::Write` sink. Every write
+/// is a whole string or a whole escaped character, so it is always valid UTF-8.
+struct FmtSink<'a, W: fmt::Write>(&'a mut W);
+
+impl<'a, W: fmt::Write> Write for FmtSink<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = std::str::from_utf8(buf)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        self.0
+            .write_str(text)
+            .map_err(|_| io::Error::from(io::ErrorKind::Other))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn write_node<W: Write>(node: &VirtualDomNode, out: &mut W) -> io::Result<()> {
+    match node {
+        VirtualDomNode::ElementNode(vnode) => {
+            write!(out, "<{}", vnode.node_type)?;
+            for attribute in vnode.attributes.iter() {
+                write_attribute(attribute, out)?;
+            }
+            out.write_all(b">")?;
+
+            if !is_void_element(&vnode.node_type) {
+                for child in vnode.children.iter() {
+                    match child {
+                        VirtualDomNode::TextNode(text_node)
+                            if is_raw_text_element(&vnode.node_type) =>
+                        {
+                            write_raw_text(&text_node.text, &vnode.node_type, out)?
+                        }
+                        child => write_node(child, out)?,
+                    }
+                }
+                write!(out, "</{}>", vnode.node_type)?;
+            }
+        }
+        VirtualDomNode::TextNode(text_node) => encode_minimal_w(&text_node.text, out)?,
+        // trusted markup, written as it is
+        VirtualDomNode::RawHtml(raw) => write!(out, "<{0}>{1}</{0}>", raw.node_type, raw.html)?,
+        VirtualDomNode::Fragment(children) => {
+            for child in children.iter() {
+                write_node(child, out)?;
+            }
+        }
+        VirtualDomNode::Empty => out.write_all(b"<!---->")?,
+    }
+    Ok(())
+}
+
+/// Writes the text of a `<script>` or `<style>` as it is, the browser doesn't
+/// decode it. A `</script` in it would end the element early, so it is written
+/// as `<\/script`, which means the same in a JavaScript string or regex.
+fn write_raw_text<W: Write>(text: &str, tag: &str, out: &mut W) -> io::Result<()> {
+    let mut rest = text;
+    while let Some(index) = rest.find("</") {
+        let (before, after) = rest.split_at(index + 2);
+        let closes = after.len() >= tag.len()
+            && after.as_bytes()[..tag.len()].eq_ignore_ascii_case(tag.as_bytes());
+        if closes {
+            out.write_all(&before.as_bytes()[..index])?;
+            out.write_all(b"<\\/")?;
+        } else {
+            out.write_all(before.as_bytes())?;
+        }
+        rest = after;
+    }
+    out.write_all(rest.as_bytes())
+}
+
+fn write_attribute<W: Write>(attribute: &Attribute, out: &mut W) -> io::Result<()> {
+    let value = match attribute.rendered_value() {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    if is_boolean_attribute(&attribute.name.to_ascii_lowercase()) {
+        return write!(out, " {}", attribute.name);
+    }
+
+    write!(out, " {}=\"", attribute.name)?;
+    encode_minimal_w(value, out)?;
+    out.write_all(b"\"")
+}
@@ -1,11 +1,21 @@
 use super::{
-    node::{Element, VirtualDomNode},
-    render::update_element,
+    diff::diff,
+    dom::{DomBackend, WebDom},
+    events::{listen_on_root, HandlerRegistry},
+    hydrate::{hydrate_with, Mismatch},
+    node::{Element, ListenerHandle, VirtualDomNode},
+    render::{apply_patches_with, call_mounted, create_node_with, remove_node_with},
 };
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 /// VirtualDom represents a virtual dom tree
 pub struct VirtualDom {
     pub node: VirtualDomNode,
+    mounted: bool,
+    handlers: Rc<RefCell<HandlerRegistry>>,
+    root_listeners: HashMap<String, ListenerHandle>,
 }
 
 impl VirtualDom {
@@ -13,13 +23,112 @@ impl VirtualDom {
     pub fn new() -> VirtualDom {
         VirtualDom {
             node: VirtualDomNode::Empty,
+            mounted: false,
+            handlers: Rc::new(RefCell::new(HandlerRegistry::default())),
+            root_listeners: HashMap::new(),
         }
     }
 
     /// Compares two virtual dom tree structures and updates the real DOM
     /// then stores the new dom tree for future comparisons
     pub fn render(&mut self, root: &mut Element, new_node: VirtualDomNode) {
-        update_element(root, 0, &new_node, &self.node);
+        match root.el.clone() {
+            Some(el) => self.render_with(&WebDom::new(), &el.into(), new_node),
+            None => self.node = new_node,
+        }
+        self.listen_for_handlers(root);
+    }
+
+    /// Renders into the DOM of any backend, like the in-memory one from `memory`.
+    /// Handlers on the virtual tree only run with `render`, in the browser.
+    pub fn render_with<B: DomBackend>(
+        &mut self,
+        dom: &B,
+        root: &B::Node,
+        new_node: VirtualDomNode,
+    ) {
+        if self.mounted {
+            let patches = diff(0, &new_node, &self.node);
+            apply_patches_with(dom, root, &patches);
+        } else {
+            // nothing is in the DOM yet, not even a placeholder for the initial empty node
+            create_node_with(dom, root, &new_node);
+            call_mounted(dom, root, &new_node);
+            self.mounted = true;
+        }
         self.node = new_node;
     }
-}
\ No newline at end of file
+
+    /// Takes over the DOM already inside `root`, such as a page rendered on the
+    /// server, instead of rendering a second copy of it. Listeners and handlers
+    /// are attached to the adopted elements and the `mounted` hooks run.
+    ///
+    /// Where the DOM doesn't match `node` it is repaired, and the mismatches
+    /// are returned so they can be logged.
+    pub fn hydrate(&mut self, root: &mut Element, node: VirtualDomNode) -> Vec<Mismatch> {
+        let mismatches = match root.el.clone() {
+            Some(el) => self.hydrate_with(&WebDom::new(), &el.into(), node),
+            None => {
+                self.node = node;
+                Vec::new()
+            }
+        };
+        self.listen_for_handlers(root);
+        mismatches
+    }
+
+    /// Hydrates the DOM of any backend, see `hydrate`
+    pub fn hydrate_with<B: DomBackend>(
+        &mut self,
+        dom: &B,
+        root: &B::Node,
+        node: VirtualDomNode,
+    ) -> Vec<Mismatch> {
+        let mismatches = hydrate_with(dom, root, &node);
+        call_mounted(dom, root, &node);
+        self.mounted = true;
+        self.node = node;
+        mismatches
+    }
+
+    /// Removes the rendered tree from `root`, calling the `before_unmount`
+    /// hooks, and stops listening on `root` for handlers. Nothing is left in
+    /// its place, a later render starts over.
+    pub fn unmount(&mut self, root: &mut Element) {
+        match root.el.clone() {
+            Some(el) => self.unmount_with(&WebDom::new(), &el.into()),
+            None => {
+                self.node = VirtualDomNode::Empty;
+                self.mounted = false;
+            }
+        }
+        self.handlers.borrow_mut().rebuild(0, &self.node);
+        self.root_listeners.clear();
+    }
+
+    /// Removes the rendered tree from the DOM of any backend, see `unmount`
+    pub fn unmount_with<B: DomBackend>(&mut self, dom: &B, root: &B::Node) {
+        if self.mounted {
+            remove_node_with(dom, root, &self.node);
+        }
+        self.mounted = false;
+        self.node = VirtualDomNode::Empty;
+    }
+
+    /// Picks up the handlers of the new tree, and listens on the root for
+    /// every event type that no handler needed before
+    fn listen_for_handlers(&mut self, root: &Element) {
+        self.handlers.borrow_mut().rebuild(0, &self.node);
+
+        let root = match &root.el {
+            Some(root) => root,
+            None => return,
+        };
+        for event_type in self.handlers.borrow().event_types() {
+            if !self.root_listeners.contains_key(&event_type) {
+                let handle = listen_on_root(root, &event_type, self.handlers.clone());
+                self.root_listeners.insert(event_type, handle);
+            }
+        }
+    }
+}
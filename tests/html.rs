@@ -0,0 +1,153 @@
+//! Compile-pass tests for the `html!` macro, every invocation in here has to
+//! expand to valid `h`/`attr`/`t` calls. Compile-fail cases live in `tests/ui`,
+//! each with the error it has to give.
+
+use humus::{html, node::VirtualDomNode};
+
+/// Prints a tree as markup so expansions can be compared as plain strings
+fn stringify(node: &VirtualDomNode) -> String {
+    match node {
+        VirtualDomNode::Empty => String::new(),
+        VirtualDomNode::TextNode(text_node) => text_node.text.clone(),
+        VirtualDomNode::Fragment(children) => children.iter().map(stringify).collect(),
+        VirtualDomNode::RawHtml(raw) => format!("<{0}>{1}</{0}>", raw.node_type, raw.html),
+        VirtualDomNode::ElementNode(vnode) => {
+            let attributes: String = vnode
+                .attributes
+                .iter()
+                .map(|attr| format!(" {}=\"{}\"", attr.name(), attr.value()))
+                .collect();
+            let children: String = vnode.children.iter().map(stringify).collect();
+            format!(
+                "<{}{}>{}</{}>",
+                vnode.node_type, attributes, children, vnode.node_type
+            )
+        }
+    }
+}
+
+#[test]
+fn single_element() {
+    let node = html! { <div></div> };
+    assert_eq!(stringify(&node), "<div></div>");
+}
+
+#[test]
+fn several_attributes() {
+    let node = html! {
+        <a href="/home" class="nav" title="Home"></a>
+    };
+    assert_eq!(
+        stringify(&node),
+        "<a href=\"/home\" class=\"nav\" title=\"Home\"></a>"
+    );
+}
+
+#[test]
+fn nested_elements_and_several_children() {
+    let node = html! {
+        <div name="main">
+            <h1 style="color:red">"Humus Virtual Dom"</h1>
+            <p>"Hello " <b>"world"</b></p>
+        </div>
+    };
+    assert_eq!(
+        stringify(&node),
+        "<div name=\"main\"><h1 style=\"color:red\">Humus Virtual Dom</h1><p>Hello <b>world</b></p></div>"
+    );
+}
+
+#[test]
+fn self_closing_and_void_elements() {
+    let node = html! {
+        <form>
+            <input type="text" name="q">
+            <br/>
+            <img src="/logo.png" alt="logo"/>
+        </form>
+    };
+    assert_eq!(
+        stringify(&node),
+        "<form><input type=\"text\" name=\"q\"></input><br></br><img src=\"/logo.png\" alt=\"logo\"></img></form>"
+    );
+}
+
+#[test]
+fn void_elements_with_a_closing_tag() {
+    let node = html! {
+        <p>
+            <input type="text"></input>
+            <br></br>
+            <br>
+            "text"
+        </p>
+    };
+    assert_eq!(
+        stringify(&node),
+        "<p><input type=\"text\"></input><br></br><br></br>text</p>"
+    );
+}
+
+#[test]
+fn fragment() {
+    let node = html! {
+        <>
+            <td>"a"</td>
+            <td>"b"</td>
+        </>
+    };
+    match &node {
+        VirtualDomNode::Fragment(children) => assert_eq!(children.len(), 2),
+        _ => panic!("expected a fragment"),
+    }
+    assert_eq!(stringify(&node), "<td>a</td><td>b</td>");
+}
+
+#[test]
+fn attribute_expressions() {
+    let cls = String::from("active");
+    let width = 42;
+    let node = html! {
+        <div class={cls} width={width}></div>
+    };
+    assert_eq!(stringify(&node), "<div class=\"active\" width=\"42\"></div>");
+    // the attribute only borrowed `cls`
+    assert_eq!(cls, "active");
+}
+
+#[test]
+fn child_expressions() {
+    let name = String::from("world");
+    let greeting = html! { <b>{&name}</b> };
+    let nothing: Option<VirtualDomNode> = None;
+    let items = vec!["a", "b"];
+
+    let node = html! {
+        <div>
+            "Hello "
+            {greeting}
+            {nothing}
+            {Some("!")}
+            <ul>{items.iter().map(|item| html! { <li>{*item}</li> })}</ul>
+        </div>
+    };
+    assert_eq!(
+        stringify(&node),
+        "<div>Hello <b>world</b>!<ul><li>a</li><li>b</li></ul></div>"
+    );
+}
+
+#[test]
+fn listener_attributes() {
+    let node = html! {
+        <button class="inc" onclick={move |_| ()}>"+"</button>
+    };
+    match &node {
+        VirtualDomNode::ElementNode(vnode) => {
+            assert_eq!(vnode.listeners.len(), 1);
+            assert_eq!(vnode.listeners[0].event(), "click");
+        }
+        _ => panic!("expected an element"),
+    }
+    assert_eq!(stringify(&node), "<button class=\"inc\">+</button>");
+}
@@ -0,0 +1,164 @@
+use humus::{
+    dom::DomBackend,
+    hydrate::Mismatch,
+    memory::{MemoryDom, MemoryNode},
+    node::VirtualDomNode,
+    render::{attr, create_node_with, dangerously_set_inner_html, fragment, h, t},
+    vdom::VirtualDom,
+};
+
+/// A root holding `node` the way the browser builds it from server-rendered HTML
+fn server_rendered(node: &VirtualDomNode) -> MemoryNode {
+    let root = MemoryNode::element("div");
+    create_node_with(&MemoryDom, &root, node);
+    root
+}
+
+fn fresh_html(node: &VirtualDomNode) -> String {
+    server_rendered(node).inner_html()
+}
+
+fn page(title: &str) -> VirtualDomNode {
+    h(
+        "main",
+        vec![
+            h("h1", vec![t(title)], vec![attr("class", "title")]),
+            fragment(vec![h("input", vec![], vec![attr("value", "x")]), t("!")]),
+            VirtualDomNode::Empty,
+            dangerously_set_inner_html("pre", "<b>code</b>"),
+        ],
+        vec![attr("id", "app")],
+    )
+}
+
+#[test]
+fn adopts_the_existing_nodes() {
+    let root = server_rendered(&page("Hello"));
+    let main = root.children()[0].clone();
+    let heading = main.children()[0].clone();
+
+    let mut vdom = VirtualDom::new();
+    let mismatches = vdom.hydrate_with(&MemoryDom, &root, page("Hello"));
+    assert_eq!(mismatches, vec![]);
+    assert_eq!(root.inner_html(), fresh_html(&page("Hello")));
+    assert!(root.children()[0].is_same_node(&main));
+
+    // later renders patch the adopted DOM
+    vdom.render_with(&MemoryDom, &root, page("Bye"));
+    assert_eq!(root.inner_html(), fresh_html(&page("Bye")));
+    assert!(root.children()[0].children()[0].is_same_node(&heading));
+}
+
+#[test]
+fn splits_texts_the_html_merged() {
+    let tree = || h("p", vec![t("Hello "), t(""), t("world"), t("")], vec![]);
+    let root = server_rendered(&h("p", vec![t("Hello world")], vec![]));
+
+    let mut vdom = VirtualDom::new();
+    assert_eq!(vdom.hydrate_with(&MemoryDom, &root, tree()), vec![]);
+    let texts: Vec<_> = root.children()[0]
+        .children()
+        .iter()
+        .map(|text| text.text().unwrap())
+        .collect();
+    assert_eq!(texts, vec!["Hello ", "", "world", ""]);
+
+    vdom.render_with(
+        &MemoryDom,
+        &root,
+        h("p", vec![t("Bye "), t(""), t("world"), t("!")], vec![]),
+    );
+    assert_eq!(root.inner_html(), "<p>Bye world!</p>");
+}
+
+#[test]
+fn boolean_attributes_only_need_to_be_present() {
+    let tree = || {
+        h(
+            "input",
+            vec![],
+            vec![attr("checked", "true"), attr("disabled", "false")],
+        )
+    };
+    let root = MemoryNode::element("div");
+    let input = MemoryDom.create_element("input");
+    MemoryDom.set_attribute(&input, "checked", "");
+    MemoryDom.append_child(&root, &input);
+
+    let mut vdom = VirtualDom::new();
+    assert_eq!(vdom.hydrate_with(&MemoryDom, &root, tree()), vec![]);
+}
+
+#[test]
+fn repairs_and_reports_mismatches() {
+    let server = h(
+        "div",
+        vec![
+            t("old"),
+            h("p", vec![], vec![attr("class", "a"), attr("title", "x")]),
+            h("em", vec![], vec![]),
+            t("left over"),
+        ],
+        vec![],
+    );
+    let client = || {
+        fragment(vec![
+            h(
+                "div",
+                vec![
+                    t("new"),
+                    h("p", vec![], vec![attr("class", "b")]),
+                    h("strong", vec![], vec![]),
+                ],
+                vec![attr("id", "x")],
+            ),
+            VirtualDomNode::Empty,
+        ])
+    };
+    let root = server_rendered(&server);
+
+    let mut vdom = VirtualDom::new();
+    let mismatches = vdom.hydrate_with(&MemoryDom, &root, client());
+    assert_eq!(
+        mismatches,
+        vec![
+            Mismatch::Attribute {
+                path: vec![0],
+                name: String::from("id"),
+                expected: Some(String::from("x")),
+                found: None,
+            },
+            Mismatch::Text {
+                path: vec![0, 0],
+                expected: String::from("new"),
+                found: String::from("old"),
+            },
+            Mismatch::Attribute {
+                path: vec![0, 1],
+                name: String::from("class"),
+                expected: Some(String::from("b")),
+                found: Some(String::from("a")),
+            },
+            Mismatch::Attribute {
+                path: vec![0, 1],
+                name: String::from("title"),
+                expected: None,
+                found: Some(String::from("x")),
+            },
+            Mismatch::Node {
+                path: vec![0, 2],
+                expected: String::from("<strong>"),
+                found: String::from("<em>"),
+            },
+            Mismatch::Extra {
+                path: vec![0, 3],
+                found: String::from("#text"),
+            },
+            Mismatch::Missing {
+                path: vec![1],
+                expected: String::from("#comment"),
+            },
+        ]
+    );
+    assert_eq!(root.inner_html(), fresh_html(&client()));
+}
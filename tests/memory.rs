@@ -0,0 +1,286 @@
+use humus::{
+    memory::{MemoryDom, MemoryNode},
+    node::VirtualDomNode,
+    render::{
+        attr, create_node_with, dangerous_attr, dangerously_set_inner_html, fragment, h, keyed, t,
+    },
+    vdom::VirtualDom,
+};
+
+fn list(keys: &[u32]) -> VirtualDomNode {
+    h(
+        "ul",
+        keys.iter()
+            .map(|key| keyed(*key, h("li", vec![t(&key.to_string())], vec![])))
+            .collect(),
+        vec![],
+    )
+}
+
+fn li(text: &str) -> VirtualDomNode {
+    h("li", vec![t(text)], vec![])
+}
+
+/// The HTML of `node` rendered from scratch, which every patched DOM has to match
+fn fresh_html(node: &VirtualDomNode) -> String {
+    let root = MemoryNode::element("div");
+    create_node_with(&MemoryDom, &root, node);
+    root.inner_html()
+}
+
+#[test]
+fn render_and_update() {
+    let root = MemoryNode::element("div");
+    let mut vdom = VirtualDom::new();
+
+    vdom.render_with(
+        &MemoryDom,
+        &root,
+        h(
+            "p",
+            vec![t("Hello "), t("world")],
+            vec![attr("class", "greeting")],
+        ),
+    );
+    assert_eq!(root.inner_html(), r#"<p class="greeting">Hello world</p>"#);
+    let paragraph = root.children()[0].clone();
+
+    vdom.render_with(
+        &MemoryDom,
+        &root,
+        h(
+            "p",
+            vec![t("Bye "), t("world")],
+            vec![attr("id", "farewell")],
+        ),
+    );
+    assert_eq!(root.inner_html(), r#"<p id="farewell">Bye world</p>"#);
+    assert!(root.children()[0].is_same_node(&paragraph));
+
+    vdom.render_with(&MemoryDom, &root, VirtualDomNode::Empty);
+    assert_eq!(root.inner_html(), "<!---->");
+}
+
+#[test]
+fn unmount_removes_the_whole_tree() {
+    let root = MemoryNode::element("div");
+    let mut vdom = VirtualDom::new();
+    let tree = || {
+        fragment(vec![
+            t("a"),
+            h("p", vec![t("b")], vec![]),
+            VirtualDomNode::Empty,
+        ])
+    };
+
+    vdom.render_with(&MemoryDom, &root, tree());
+    assert_eq!(root.inner_html(), "a<p>b</p><!---->");
+    vdom.unmount_with(&MemoryDom, &root);
+    assert_eq!(root.inner_html(), "");
+
+    // the next render starts over
+    vdom.render_with(&MemoryDom, &root, tree());
+    assert_eq!(root.inner_html(), "a<p>b</p><!---->");
+}
+
+#[test]
+fn escapes_text_and_attributes() {
+    let node = h(
+        "a",
+        vec![t("<b> & </b>"), h("br", vec![], vec![])],
+        vec![attr("title", r#"say "hi" & co"#)],
+    );
+    assert_eq!(
+        fresh_html(&node),
+        r#"<a title="say &quot;hi&quot; &amp; co">&lt;b&gt; &amp; &lt;/b&gt;<br></a>"#
+    );
+}
+
+#[test]
+fn false_boolean_attributes_are_left_out() {
+    let input = |checked: &str, disabled: &str| {
+        h(
+            "input",
+            vec![],
+            vec![attr("checked", checked), attr("disabled", disabled)],
+        )
+    };
+    let root = MemoryNode::element("div");
+    let mut vdom = VirtualDom::new();
+
+    vdom.render_with(&MemoryDom, &root, input("false", "false"));
+    assert_eq!(root.inner_html(), "<input>");
+    assert_eq!(root.inner_html(), input("false", "false").to_html());
+
+    vdom.render_with(&MemoryDom, &root, input("true", ""));
+    assert_eq!(root.inner_html(), r#"<input checked="true" disabled="">"#);
+
+    vdom.render_with(&MemoryDom, &root, input("false", "false"));
+    assert_eq!(root.inner_html(), "<input>");
+}
+
+#[test]
+fn guards_against_script_attributes() {
+    let link = |href: &str| {
+        h(
+            "a",
+            vec![t("link")],
+            vec![
+                attr("href", href),
+                attr("onclick", "steal()"),
+                attr("OnMouseOver", "steal()"),
+            ],
+        )
+    };
+    let root = MemoryNode::element("div");
+    let mut vdom = VirtualDom::new();
+
+    vdom.render_with(&MemoryDom, &root, link(" JavaScript:steal()"));
+    assert_eq!(root.inner_html(), r#"<a href="about:blank">link</a>"#);
+
+    vdom.render_with(&MemoryDom, &root, link("data:text/html,<script>"));
+    assert_eq!(root.inner_html(), r#"<a href="about:blank">link</a>"#);
+
+    vdom.render_with(&MemoryDom, &root, link("/javascript:"));
+    assert_eq!(root.inner_html(), r#"<a href="/javascript:">link</a>"#);
+
+    // trusted values are rendered as they are, and removed once they turn untrusted
+    vdom.render_with(
+        &MemoryDom,
+        &root,
+        h(
+            "a",
+            vec![t("link")],
+            vec![
+                dangerous_attr("href", "javascript:void(0)"),
+                dangerous_attr("onclick", "track()"),
+            ],
+        ),
+    );
+    assert_eq!(
+        root.inner_html(),
+        r#"<a href="javascript:void(0)" onclick="track()">link</a>"#
+    );
+
+    vdom.render_with(&MemoryDom, &root, link("javascript:void(0)"));
+    assert_eq!(root.inner_html(), r#"<a href="about:blank">link</a>"#);
+}
+
+#[test]
+fn raw_html_is_diffed_by_its_string() {
+    let root = MemoryNode::element("div");
+    let mut vdom = VirtualDom::new();
+    let code = |html: &str| dangerously_set_inner_html("pre", html);
+
+    vdom.render_with(&MemoryDom, &root, code("<b>fn</b> main() {}"));
+    assert_eq!(root.inner_html(), "<pre><b>fn</b> main() {}</pre>");
+    let pre = root.children()[0].clone();
+    let content = pre.children()[0].clone();
+
+    // the same string leaves the content alone, another one replaces it
+    vdom.render_with(&MemoryDom, &root, code("<b>fn</b> main() {}"));
+    assert!(pre.children()[0].is_same_node(&content));
+    vdom.render_with(&MemoryDom, &root, code("<i>let</i> x;"));
+    assert_eq!(root.inner_html(), "<pre><i>let</i> x;</pre>");
+    assert!(root.children()[0].is_same_node(&pre));
+
+    vdom.render_with(&MemoryDom, &root, dangerously_set_inner_html("code", "x"));
+    assert_eq!(root.inner_html(), "<code>x</code>");
+}
+
+#[test]
+fn keyed_reorder_keeps_nodes() {
+    let root = MemoryNode::element("div");
+    let mut vdom = VirtualDom::new();
+
+    vdom.render_with(&MemoryDom, &root, list(&[1, 2, 3, 4, 5]));
+    let items = root.children()[0].children();
+
+    vdom.render_with(&MemoryDom, &root, list(&[5, 3, 1, 6, 4]));
+    assert_eq!(
+        root.inner_html(),
+        "<ul><li>5</li><li>3</li><li>1</li><li>6</li><li>4</li></ul>"
+    );
+    let reordered = root.children()[0].children();
+    assert!(reordered[0].is_same_node(&items[4]));
+    assert!(reordered[1].is_same_node(&items[2]));
+    assert!(reordered[2].is_same_node(&items[0]));
+    assert!(reordered[4].is_same_node(&items[3]));
+}
+
+#[test]
+fn patched_dom_matches_a_fresh_render() {
+    let trees = || {
+        vec![
+            h("div", vec![t("a"), list(&[1, 2, 3])], vec![attr("id", "x")]),
+            h(
+                "div",
+                vec![fragment(vec![t("b"), VirtualDomNode::Empty]), list(&[3, 1])],
+                vec![],
+            ),
+            h(
+                "div",
+                vec![
+                    fragment(vec![]),
+                    h("span", vec![t("c")], vec![attr("class", "y")]),
+                    list(&[2, 4, 1, 3]),
+                ],
+                vec![attr("id", "z")],
+            ),
+            fragment(vec![t("d"), h("p", vec![], vec![]), t("e")]),
+            h("section", vec![fragment(vec![t("f"), t("g")])], vec![]),
+            h(
+                "div",
+                vec![t("a"), dangerously_set_inner_html("pre", "<b>h</b>")],
+                vec![],
+            ),
+            // keyed rows mixed with unkeyed ones, duplicate keys and fragments
+            h("ul", vec![keyed(1, li("a")), li("b"), li("c")], vec![]),
+            h("ul", vec![keyed(1, li("a")), li("d")], vec![]),
+            h("ul", vec![keyed(1, li("a")), keyed(1, li("b"))], vec![]),
+            h("ul", vec![keyed(1, li("c"))], vec![]),
+            h(
+                "ul",
+                vec![
+                    fragment(vec![t("x"), t("y")]),
+                    keyed(1, li("1")),
+                    keyed(2, li("2")),
+                ],
+                vec![],
+            ),
+            h(
+                "ul",
+                vec![
+                    fragment(vec![t("x"), t("y")]),
+                    keyed(2, li("2")),
+                    keyed(1, li("1")),
+                ],
+                vec![],
+            ),
+            VirtualDomNode::Empty,
+            h("div", vec![t("a"), list(&[1, 2, 3])], vec![attr("id", "x")]),
+        ]
+    };
+
+    // every tree rendered after every other one
+    let count = trees().len();
+    for i in 0..count {
+        for j in 0..count {
+            let old = trees().remove(i);
+            let new = trees().remove(j);
+            let root = MemoryNode::element("div");
+            let mut vdom = VirtualDom::new();
+            let expected = fresh_html(&new);
+
+            vdom.render_with(&MemoryDom, &root, old);
+            vdom.render_with(&MemoryDom, &root, new);
+            assert_eq!(
+                root.inner_html(),
+                expected,
+                "rendering tree {} after {}",
+                j,
+                i
+            );
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use humus::{
+    node::VirtualDomNode,
+    parse::{parse_html, ParseError, ParseErrorKind, MAX_DEPTH},
+    render::fragment,
+};
+
+/// Parses `html` and renders it back, which shows what the parsed tree holds
+fn round_trip(html: &str) -> String {
+    fragment(parse_html(html).unwrap()).to_html()
+}
+
+fn error(html: &str) -> ParseError {
+    match parse_html(html) {
+        Ok(nodes) => panic!("{:?} parsed into {:?}", html, nodes),
+        Err(error) => error,
+    }
+}
+
+#[test]
+fn elements_attributes_and_text() {
+    let nodes = parse_html(r#"<p class="intro" id=first>Hello <b>world</b></p><hr>"#).unwrap();
+    assert_eq!(nodes.len(), 2);
+    match &nodes[0] {
+        VirtualDomNode::ElementNode(p) => {
+            assert_eq!(p.node_type, "p");
+            let attributes: Vec<_> = p
+                .attributes
+                .iter()
+                .map(|attribute| format!("{}={}", attribute.name(), attribute.value()))
+                .collect();
+            assert_eq!(attributes, vec!["class=intro", "id=first"]);
+            assert_eq!(p.children.len(), 2);
+        }
+        node => panic!("expected <p>, got {:?}", node),
+    }
+    assert_eq!(
+        fragment(nodes).to_html(),
+        r#"<p class="intro" id="first">Hello <b>world</b></p><hr>"#
+    );
+}
+
+#[test]
+fn void_and_self_closing_elements() {
+    assert_eq!(
+        round_trip(r#"<img src="a.png"><br/><input disabled><div/>text"#),
+        r#"<img src="a.png"><br><input disabled><div></div>text"#
+    );
+}
+
+#[test]
+fn decodes_entities() {
+    let nodes = parse_html(
+        r#"<a title="&quot;x&quot; &#38; y">Fish &amp; chips &lt;3 &#x263A; &copy;</a>"#,
+    )
+    .unwrap();
+    match &nodes[0] {
+        VirtualDomNode::ElementNode(a) => {
+            assert_eq!(a.attributes[0].value(), r#""x" & y"#);
+            match &a.children[0] {
+                VirtualDomNode::TextNode(text) => assert_eq!(text.text, "Fish & chips <3 ☺ ©"),
+                node => panic!("expected text, got {:?}", node),
+            }
+        }
+        node => panic!("expected <a>, got {:?}", node),
+    }
+    // an `&` or `<` that starts nothing stays in the text
+    assert_eq!(round_trip("Tom & Jerry < 3"), "Tom &amp; Jerry &lt; 3");
+}
+
+#[test]
+fn skips_comments_and_doctypes_and_keeps_raw_text() {
+    assert_eq!(
+        round_trip("<!DOCTYPE html><!-- a <b> comment --><P>x</p>"),
+        "<p>x</p>"
+    );
+    let nodes = parse_html("<script>if (a < b && c) {}</script><style></style>").unwrap();
+    assert_eq!(
+        fragment(nodes).to_html(),
+        "<script>if (a < b && c) {}</script><style></style>"
+    );
+}
+
+#[test]
+fn reports_malformed_html() {
+    assert_eq!(
+        error("<div>\n  <p>text</div>"),
+        ParseError {
+            kind: ParseErrorKind::UnexpectedClosingTag(String::from("div")),
+            line: 2,
+            column: 10,
+        }
+    );
+    assert_eq!(
+        error("<ul><li>one</li>").kind,
+        ParseErrorKind::UnclosedElement(String::from("ul"))
+    );
+    assert_eq!(
+        error("</p>").kind,
+        ParseErrorKind::UnexpectedClosingTag(String::from("p"))
+    );
+    assert_eq!(error(r#"<a href="x>"#).kind, ParseErrorKind::UnexpectedEnd);
+    assert_eq!(error("<!-- open").kind, ParseErrorKind::UnexpectedEnd);
+    assert_eq!(
+        error(r#"<a ="x">"#).kind,
+        ParseErrorKind::InvalidAttribute('=')
+    );
+    assert_eq!(
+        error("a &nosuch; b").kind,
+        ParseErrorKind::InvalidEntity(String::from("&nosuch;"))
+    );
+    assert_eq!(
+        error("<div>\n  <p>text</div>").to_string(),
+        "</div> doesn't close an open element at line 2, column 10"
+    );
+}
+
+#[test]
+fn limits_how_deep_elements_nest() {
+    let nested = |depth: usize| "<b>".repeat(depth) + &"</b>".repeat(depth);
+    assert_eq!(round_trip(&nested(MAX_DEPTH)), nested(MAX_DEPTH));
+    assert_eq!(
+        error(&nested(MAX_DEPTH + 1)),
+        ParseError {
+            kind: ParseErrorKind::TooDeep,
+            line: 1,
+            column: 3 * MAX_DEPTH + 1,
+        }
+    );
+    // far deeper than the stack would allow without the limit
+    assert_eq!(error(&nested(100_000)).kind, ParseErrorKind::TooDeep);
+    // void and raw text elements don't nest
+    assert!(parse_html(&"<br>".repeat(100_000)).is_ok());
+}
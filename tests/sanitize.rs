@@ -0,0 +1,182 @@
+use humus::{
+    parse::{ParseErrorKind, MAX_DEPTH},
+    render::{attr, dangerous_attr, dangerously_set_inner_html, fragment, h, t},
+    sanitize::{Removal, Sanitizer},
+};
+
+/// The HTML left after sanitizing `html` with `sanitizer`, and what was removed
+fn sanitize(sanitizer: &Sanitizer, html: &str) -> (String, Vec<Removal>) {
+    let sanitized = sanitizer.sanitize(html).unwrap();
+    (fragment(sanitized.nodes).to_html(), sanitized.removed)
+}
+
+fn attribute(tag: &str, name: &str) -> Removal {
+    Removal::Attribute {
+        tag: String::from(tag),
+        name: String::from(name),
+    }
+}
+
+#[test]
+fn keeps_allowed_markup() {
+    let html = r#"<p>Some <b>bold</b> text, <a href="https://example.com" title="x">a link</a><br><img src="a.png" alt="a"></p>"#;
+    assert_eq!(
+        sanitize(&Sanitizer::new(), html),
+        (String::from(html), vec![])
+    );
+}
+
+#[test]
+fn removes_scripts_styles_and_handlers() {
+    let (html, removed) = sanitize(
+        &Sanitizer::new(),
+        r#"<p onclick="steal()" ONMOUSEOVER="steal()">hi<script>steal()</script></p><style>p { color: red }</style>"#,
+    );
+    assert_eq!(html, "<p>hi</p>");
+    assert_eq!(
+        removed,
+        vec![
+            attribute("p", "onclick"),
+            attribute("p", "onmouseover"),
+            Removal::Element(String::from("script")),
+            Removal::Element(String::from("style")),
+        ]
+    );
+}
+
+#[test]
+fn removes_javascript_urls() {
+    let (html, removed) = sanitize(
+        &Sanitizer::new(),
+        "<a href=\"  JavaScript:steal()\">a</a><a href=\"java&#9;script:steal()\">b</a><a href=\"/javascript:\">c</a>",
+    );
+    assert_eq!(html, r#"<a>a</a><a>b</a><a href="/javascript:">c</a>"#);
+    assert_eq!(
+        removed,
+        vec![
+            Removal::Url {
+                tag: String::from("a"),
+                name: String::from("href"),
+                value: String::from("  JavaScript:steal()"),
+            },
+            Removal::Url {
+                tag: String::from("a"),
+                name: String::from("href"),
+                value: String::from("java\tscript:steal()"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn unwraps_tags_that_are_not_allowed() {
+    let (html, removed) = sanitize(
+        &Sanitizer::new(),
+        r#"<div class="box"><p style="color: red">one</p><iframe src="x"></iframe>two</div>"#,
+    );
+    assert_eq!(html, "<p>one</p>two");
+    assert_eq!(
+        removed,
+        vec![
+            Removal::Tag(String::from("div")),
+            attribute("p", "style"),
+            Removal::Tag(String::from("iframe")),
+        ]
+    );
+}
+
+#[test]
+fn allowlist_is_configurable() {
+    let sanitizer = Sanitizer::empty()
+        .allow_tags(&["div", "em", "strong"])
+        .deny_tags(&["strong"])
+        .allow_attributes(&["class", "onclick", "title"])
+        .deny_attributes(&["title"]);
+    let (html, removed) = sanitize(
+        &sanitizer,
+        r#"<div class="a" title="b" onclick="c()"><em>x</em><strong>y</strong></div>"#,
+    );
+    assert_eq!(html, r#"<div class="a"><em>x</em>y</div>"#);
+    assert_eq!(
+        removed,
+        vec![
+            attribute("div", "title"),
+            attribute("div", "onclick"),
+            Removal::Tag(String::from("strong")),
+        ]
+    );
+
+    // only text is left without any allowed tags
+    let (html, _) = sanitize(&Sanitizer::empty(), "<p>a <b>b</b></p>");
+    assert_eq!(html, "a b");
+}
+
+#[test]
+fn sanitizes_nodes_built_in_code() {
+    let sanitized = Sanitizer::new().sanitize_nodes(vec![h(
+        "p",
+        vec![
+            fragment(vec![t("a"), h("script", vec![t("x")], vec![])]),
+            h("a", vec![t("b")], vec![attr("href", "javascript:x")]),
+            dangerously_set_inner_html("span", "<em onclick=\"x\">c</em><script>x</script>"),
+            dangerously_set_inner_html("span", "<em>unclosed"),
+        ],
+        vec![attr("onclick", "x")],
+    )]);
+    assert_eq!(
+        fragment(sanitized.nodes).to_html(),
+        "<p>a<a>b</a><span><em>c</em></span></p>"
+    );
+    assert_eq!(sanitized.removed.len(), 6);
+}
+
+#[test]
+fn refuses_deeply_nested_markup() {
+    let hostile = "<div><b>".repeat(50_000) + "x";
+    let error = Sanitizer::new().sanitize(&hostile).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::TooDeep);
+
+    // nodes built in code are cut off at the same depth
+    let mut node = t("x");
+    for _ in 0..100_000 {
+        node = h("span", vec![node], vec![]);
+    }
+    let sanitized = Sanitizer::new().sanitize_nodes(vec![node]);
+    let html = fragment(sanitized.nodes).to_html();
+    assert_eq!(
+        html,
+        "<span>".repeat(MAX_DEPTH) + &"</span>".repeat(MAX_DEPTH)
+    );
+    assert_eq!(
+        sanitized.removed,
+        vec![Removal::TooDeep(String::from("span"))]
+    );
+}
+
+#[test]
+fn trusted_attributes_are_checked_too() {
+    let sanitizer = Sanitizer::new().allow_attributes(&["class", "onclick"]);
+    let sanitized = sanitizer.sanitize_nodes(vec![h(
+        "a",
+        vec![t("a")],
+        vec![
+            dangerous_attr("href", "data:text/html,<script>steal()</script>"),
+            dangerous_attr("onclick", "steal()"),
+            dangerous_attr("class", "link"),
+            dangerous_attr("title", "Data:yes"),
+        ],
+    )]);
+    assert_eq!(
+        sanitized.removed,
+        vec![
+            Removal::Url {
+                tag: String::from("a"),
+                name: String::from("href"),
+                value: String::from("data:text/html,<script>steal()</script>"),
+            },
+            attribute("a", "onclick"),
+        ]
+    );
+    let html = fragment(sanitized.nodes).to_html();
+    assert_eq!(html, r#"<a class="link" title="Data:yes">a</a>"#);
+}
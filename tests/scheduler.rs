@@ -0,0 +1,53 @@
+use humus::scheduler::Scheduler;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+#[test]
+fn renders_right_away_outside_the_browser() {
+    let renders = Rc::new(Cell::new(0));
+    let counter = renders.clone();
+    let scheduler = Scheduler::new(move || counter.set(counter.get() + 1));
+
+    scheduler.schedule_render();
+    assert_eq!(renders.get(), 1);
+    assert!(!scheduler.is_dirty());
+
+    scheduler.schedule_render();
+    assert_eq!(renders.get(), 2);
+}
+
+#[test]
+fn flush_without_a_scheduled_render() {
+    let renders = Rc::new(Cell::new(0));
+    let counter = renders.clone();
+    let scheduler = Scheduler::new(move || counter.set(counter.get() + 1));
+
+    scheduler.flush();
+    assert_eq!(renders.get(), 0);
+}
+
+#[test]
+fn render_scheduled_while_rendering_runs_after_it() {
+    let log = Rc::new(RefCell::new(vec![]));
+    let slot: Rc<RefCell<Option<Scheduler>>> = Rc::new(RefCell::new(None));
+
+    let render_log = log.clone();
+    let render_slot = slot.clone();
+    let scheduler = Scheduler::new(move || {
+        let first = render_log.borrow().is_empty();
+        render_log.borrow_mut().push("start");
+        if first {
+            // scheduling twice from inside a render still means a single render after it
+            let scheduler = render_slot.borrow().clone().unwrap();
+            scheduler.schedule_render();
+            scheduler.schedule_render();
+        }
+        render_log.borrow_mut().push("end");
+    });
+    *slot.borrow_mut() = Some(scheduler.clone());
+
+    scheduler.schedule_render();
+    assert_eq!(*log.borrow(), vec!["start", "end", "start", "end"]);
+
+    slot.borrow_mut().take();
+}
@@ -0,0 +1,239 @@
+use humus::{
+    html,
+    node::VirtualDomNode,
+    render::{
+        attr, dangerous_attr, dangerously_set_inner_html, fragment, h, listener, t, with_listeners,
+    },
+    ssr::{render_to_string, HtmlWriter},
+};
+use std::io;
+
+#[test]
+fn elements_attributes_and_text() {
+    let node = h(
+        "div",
+        vec![h("h1", vec![t("Humus")], vec![]), t("Virtual Dom")],
+        vec![attr("id", "main"), attr("class", "page wide")],
+    );
+    assert_eq!(
+        render_to_string(&node),
+        r#"<div id="main" class="page wide"><h1>Humus</h1>Virtual Dom</div>"#
+    );
+}
+
+#[test]
+fn escapes_text_and_attribute_values() {
+    let node = h(
+        "p",
+        vec![t("<script>alert('&')</script>")],
+        vec![attr("title", r#"a "quoted" <title>"#)],
+    );
+    assert_eq!(
+        node.to_html(),
+        "<p title=\"a &quot;quoted&quot; &lt;title&gt;\">&lt;script&gt;alert(&#x27;&amp;&#x27;)&lt;/script&gt;</p>"
+    );
+}
+
+#[test]
+fn script_and_style_text_is_not_escaped() {
+    let node = fragment(vec![
+        h(
+            "script",
+            vec![t("if (a < b && c) { s = '</script><b>'; t = '</SCRIPT' }")],
+            vec![],
+        ),
+        h("style", vec![t("a > b { content: '</style>' }")], vec![]),
+    ]);
+    assert_eq!(
+        node.to_html(),
+        r#"<script>if (a < b && c) { s = '<\/script><b>'; t = '<\/SCRIPT' }</script><style>a > b { content: '<\/style>' }</style>"#
+    );
+}
+
+#[test]
+fn void_elements_have_no_closing_tag() {
+    let node = h(
+        "p",
+        vec![
+            t("one"),
+            h("br", vec![], vec![]),
+            h("img", vec![], vec![attr("src", "a.png")]),
+        ],
+        vec![],
+    );
+    assert_eq!(node.to_html(), r#"<p>one<br><img src="a.png"></p>"#);
+}
+
+#[test]
+fn boolean_attributes() {
+    let node = h(
+        "input",
+        vec![],
+        vec![
+            attr("type", "checkbox"),
+            attr("checked", "true"),
+            attr("disabled", ""),
+            attr("required", "false"),
+            attr("ReadOnly", ""),
+            attr("Hidden", "false"),
+        ],
+    );
+    assert_eq!(
+        node.to_html(),
+        r#"<input type="checkbox" checked disabled ReadOnly>"#
+    );
+}
+
+#[test]
+fn script_attributes_are_not_written() {
+    let node = h(
+        "img",
+        vec![],
+        vec![
+            attr("src", "data:image/png;base64,AAAA"),
+            attr("onerror", "steal()"),
+            dangerous_attr("onload", "track()"),
+        ],
+    );
+    assert_eq!(
+        node.to_html(),
+        r#"<img src="about:blank" onload="track()">"#
+    );
+}
+
+#[test]
+fn raw_html_is_written_as_it_is() {
+    let node = h(
+        "figure",
+        vec![dangerously_set_inner_html(
+            "div",
+            r#"<svg viewBox="0 0 1 1"><path d="M0 0"/></svg> & more"#,
+        )],
+        vec![],
+    );
+    assert_eq!(
+        node.to_html(),
+        r#"<figure><div><svg viewBox="0 0 1 1"><path d="M0 0"/></svg> & more</div></figure>"#
+    );
+}
+
+#[test]
+fn fragments_empty_nodes_and_listeners() {
+    let button = with_listeners(
+        vec![listener("click", |_| {})],
+        h("button", vec![t("ok")], vec![]),
+    );
+    let node = fragment(vec![t("a"), VirtualDomNode::Empty, button]);
+    assert_eq!(node.to_html(), "a<!----><button>ok</button>");
+}
+
+#[test]
+fn renders_html_macro_output() {
+    let items = ["one", "two"];
+    let node = html! {
+        <ul class="list">
+            {items.iter().map(|item| html! { <li>{*item}</li> })}
+        </ul>
+    };
+    assert_eq!(
+        node.to_html(),
+        r#"<ul class="list"><li>one</li><li>two</li></ul>"#
+    );
+}
+
+fn page() -> VirtualDomNode {
+    let rows: Vec<VirtualDomNode> = (0..100)
+        .map(|i| {
+            h(
+                "tr",
+                vec![
+                    h("td", vec![t(&format!("row {} <&> \"'", i))], vec![]),
+                    h(
+                        "td",
+                        vec![h("input", vec![], vec![attr("checked", "true")])],
+                        vec![],
+                    ),
+                ],
+                vec![attr("data-row", &i.to_string())],
+            )
+        })
+        .collect();
+    h(
+        "html",
+        vec![h(
+            "body",
+            vec![h("table", rows, vec![]), VirtualDomNode::Empty],
+            vec![],
+        )],
+        vec![attr("lang", "en")],
+    )
+}
+
+#[test]
+fn streams_the_same_bytes_as_the_string_renderer() {
+    let page = page();
+    let expected = render_to_string(&page);
+
+    let mut bytes: Vec<u8> = Vec::new();
+    HtmlWriter::new().write_to(&page, &mut bytes).unwrap();
+    assert_eq!(String::from_utf8(bytes).unwrap(), expected);
+
+    let mut text = String::new();
+    HtmlWriter::new().write_to_fmt(&page, &mut text).unwrap();
+    assert_eq!(text, expected);
+}
+
+#[test]
+fn doctype_preamble() {
+    let page = page();
+
+    let mut bytes: Vec<u8> = Vec::new();
+    HtmlWriter::new()
+        .doctype(true)
+        .write_to(&page, &mut bytes)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(bytes).unwrap(),
+        format!("<!DOCTYPE html>{}", render_to_string(&page))
+    );
+}
+
+/// Counts the writes and fails once it had enough of them
+struct LimitedSink {
+    writes: usize,
+    limit: usize,
+}
+
+impl io::Write for LimitedSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.writes == self.limit {
+            return Err(io::Error::from(io::ErrorKind::WriteZero));
+        }
+        self.writes += 1;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn writes_chunk_by_chunk_and_stops_on_errors() {
+    let page = page();
+
+    let mut sink = LimitedSink {
+        writes: 0,
+        limit: usize::MAX,
+    };
+    HtmlWriter::new().write_to(&page, &mut sink).unwrap();
+    assert!(sink.writes > 100);
+
+    let mut sink = LimitedSink {
+        writes: 0,
+        limit: 10,
+    };
+    let error = HtmlWriter::new().write_to(&page, &mut sink).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+    assert_eq!(sink.writes, 10);
+}
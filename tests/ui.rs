@@ -0,0 +1,7 @@
+/// `html!` rejects bad markup with an error pointing at the offending token,
+/// the expected errors are next to each case in `tests/ui`
+#[test]
+fn html_macro_errors() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let cls = "big";
+    let _ = humus::html! { <div class=cls></div> };
+}
@@ -0,0 +1,3 @@
+fn main() {
+    let _ = humus::html! { <div>42</div> };
+}
@@ -1,13 +1,15 @@
 use log::*;
-use wasm_bindgen::JsCast;
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_test::*;
 use web_sys;
 
 use humus::{
+    component::{embed, App, Component, Link},
+    diff::diff,
     html,
-    node::Element,
+    node::{Element, VirtualDomNode},
     vdom::VirtualDom,
-    render::{h,t, attr}
+    render::{h,t, attr, keyed, fragment, listener, with_listeners, handler, capture_handler, with_handlers}
 
 };
 
@@ -51,16 +53,23 @@ fn _stringify_humus_node(n: Element) -> String {
     n.inner_html()
 }
 
-#[test]
+#[wasm_bindgen_test]
 pub fn test_jsx() {
-    // ignore html! errors!!!
-    html!{
-        <div name="main">
-            <h1 style="color:red">"Humus Virtual Dom"</h1>
-        </div>
-    };
-    eprint!("{:?}", x);
-    assert_eq!(2, 3)
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+
+    vd.render(
+        &mut root,
+        html! {
+            <div name="main">
+                <h1 style="color:red">"Humus Virtual Dom"</h1>
+            </div>
+        },
+    );
+    assert_eq!(
+        root.inner_html(),
+        String::from("<div name=\"main\"><h1 style=\"color:red\">Humus Virtual Dom</h1></div>")
+    );
 }
 
 #[wasm_bindgen_test]
@@ -105,6 +114,581 @@ pub fn render_test() {
     );
 }
 
+#[wasm_bindgen_test]
+pub fn rerender_unchanged_tree_test() {
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+
+    let app = || {
+        h(
+            "div",
+            vec![h(
+                "h1",
+                vec![t("Humus Virtual Dom")],
+                vec![attr("style", "color:red")],
+            )],
+            vec![attr("name", "glavni-div")],
+        )
+    };
+
+    vd.render(&mut root, app());
+    let rendered = root.clone().inner_html();
+
+    let next = app();
+    assert!(diff(0, &next, &vd.node).is_empty());
+    vd.render(&mut root, next);
+    assert_eq!(rendered, root.inner_html());
+}
+
+#[wasm_bindgen_test]
+pub fn update_changed_nodes_test() {
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+
+    vd.render(
+        &mut root,
+        h(
+            "ul",
+            vec![
+                h("li", vec![t("1")], vec![]),
+                h("li", vec![t("2")], vec![attr("class", "odd")]),
+            ],
+            vec![],
+        ),
+    );
+    vd.render(
+        &mut root,
+        h(
+            "ul",
+            vec![h("li", vec![t("3")], vec![attr("class", "even")])],
+            vec![],
+        ),
+    );
+    assert_eq!(
+        root.inner_html(),
+        String::from("<ul><li class=\"even\">3</li></ul>")
+    );
+}
+
+#[wasm_bindgen_test]
+pub fn keyed_reorder_test() {
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+
+    let list = |keys: &[u32]| {
+        h(
+            "ul",
+            keys.iter()
+                .map(|key| keyed(*key, h("li", vec![t(&key.to_string())], vec![])))
+                .collect(),
+            vec![],
+        )
+    };
+
+    vd.render(&mut root, list(&[1, 2, 3, 4]));
+    let ul = root.el.as_ref().unwrap().first_element_child().unwrap();
+    let fourth = ul.last_element_child().unwrap();
+
+    vd.render(&mut root, list(&[4, 1, 3, 5]));
+    assert_eq!(
+        root.clone().inner_html(),
+        String::from("<ul><li>4</li><li>1</li><li>3</li><li>5</li></ul>")
+    );
+    // the moved row keeps its real DOM node
+    assert!(fourth.is_same_node(ul.first_child().as_ref()));
+}
+
+#[wasm_bindgen_test]
+pub fn attribute_diff_test() {
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+
+    vd.render(
+        &mut root,
+        h(
+            "input",
+            vec![],
+            vec![attr("class", "big"), attr("disabled", ""), attr("value", "a")],
+        ),
+    );
+    let input = root.el.as_ref().unwrap().first_element_child().unwrap();
+    js_sys::Reflect::set(&input, &"value".into(), &"typed by the user".into()).unwrap();
+
+    vd.render(
+        &mut root,
+        h("input", vec![], vec![attr("class", "small"), attr("value", "a")]),
+    );
+    assert_eq!(input.get_attribute("class"), Some(String::from("small")));
+    assert!(!input.has_attribute("disabled"));
+    assert_eq!(
+        js_sys::Reflect::get(&input, &"value".into()).unwrap(),
+        JsValue::from_str("a")
+    );
+}
+
+#[wasm_bindgen_test]
+pub fn mixed_text_and_elements_test() {
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+
+    let greeting = |name: &str| h("p", vec![t("Hello "), h("b", vec![t(name)], vec![])], vec![]);
+
+    vd.render(&mut root, greeting("world"));
+    assert_eq!(
+        root.clone().inner_html(),
+        String::from("<p>Hello <b>world</b></p>")
+    );
+    let p = root.el.as_ref().unwrap().first_element_child().unwrap();
+    let hello = p.first_child().unwrap();
+
+    vd.render(&mut root, greeting("humus"));
+    assert_eq!(
+        root.clone().inner_html(),
+        String::from("<p>Hello <b>humus</b></p>")
+    );
+    assert!(hello.is_same_node(p.first_child().as_ref()));
+}
+
+#[wasm_bindgen_test]
+pub fn empty_placeholder_test() {
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+
+    let page = |logged_in: bool| {
+        h(
+            "div",
+            vec![
+                if logged_in {
+                    h("span", vec![t("Welcome")], vec![])
+                } else {
+                    VirtualDomNode::Empty
+                },
+                h("p", vec![t("content")], vec![]),
+            ],
+            vec![],
+        )
+    };
+
+    vd.render(&mut root, page(false));
+    assert_eq!(
+        root.clone().inner_html(),
+        String::from("<div><!----><p>content</p></div>")
+    );
+
+    vd.render(&mut root, page(true));
+    assert_eq!(
+        root.clone().inner_html(),
+        String::from("<div><span>Welcome</span><p>content</p></div>")
+    );
+}
+
+#[wasm_bindgen_test]
+pub fn fragment_test() {
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+
+    let row = |cells: &[&str]| {
+        h(
+            "tr",
+            vec![fragment(
+                cells.iter().map(|cell| h("td", vec![t(cell)], vec![])).collect(),
+            )],
+            vec![],
+        )
+    };
+
+    vd.render(&mut root, row(&["a", "b"]));
+    assert_eq!(
+        root.clone().inner_html(),
+        String::from("<tr><td>a</td><td>b</td></tr>")
+    );
+
+    vd.render(&mut root, row(&["a", "c", "d"]));
+    assert_eq!(
+        root.clone().inner_html(),
+        String::from("<tr><td>a</td><td>c</td><td>d</td></tr>")
+    );
+}
+
+#[wasm_bindgen_test]
+pub fn listener_test() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+    let clicks = Rc::new(Cell::new(0));
+
+    let button = |step: Option<u32>| {
+        let clicks = clicks.clone();
+        let listeners = match step {
+            Some(step) => vec![listener("click", move |_| clicks.set(clicks.get() + step))],
+            None => vec![],
+        };
+        with_listeners(listeners, h("button", vec![t("+")], vec![]))
+    };
+    let click = |root: &Element| {
+        root.el
+            .as_ref()
+            .unwrap()
+            .first_element_child()
+            .unwrap()
+            .dyn_into::<web_sys::HtmlElement>()
+            .unwrap()
+            .click()
+    };
+
+    vd.render(&mut root, button(Some(1)));
+    click(&root);
+    assert_eq!(clicks.get(), 1);
+
+    vd.render(&mut root, button(Some(10)));
+    click(&root);
+    assert_eq!(clicks.get(), 11);
+
+    vd.render(&mut root, button(None));
+    click(&root);
+    assert_eq!(clicks.get(), 11);
+}
+
+#[wasm_bindgen_test]
+pub fn synthetic_event_test() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut root: Element = create_element("div").into();
+    let mut vd = VirtualDom::new();
+    let log = Rc::new(RefCell::new(vec![]));
+
+    let tree = |stop: bool| {
+        let record = |entry: &'static str| {
+            let log = log.clone();
+            move |_: &humus::events::SyntheticEvent| log.borrow_mut().push(entry)
+        };
+        let log = log.clone();
+        let button = with_handlers(
+            vec![handler("click", move |event| {
+                log.borrow_mut().push("button");
+                if stop {
+                    event.stop_propagation();
+                }
+            })],
+            h("button", vec![t("+")], vec![]),
+        );
+        with_handlers(
+            vec![
+                handler("click", record("outer")),
+                capture_handler("click", record("capture outer")),
+            ],
+            h("div", vec![fragment(vec![h("span", vec![], vec![]), button])], vec![]),
+        )
+    };
+    let click = |root: &Element| {
+        root.el
+            .as_ref()
+            .unwrap()
+            .query_selector("button")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<web_sys::HtmlElement>()
+            .unwrap()
+            .click()
+    };
+
+    vd.render(&mut root, tree(false));
+    click(&root);
+    assert_eq!(*log.borrow(), vec!["capture outer", "button", "outer"]);
+
+    log.borrow_mut().clear();
+    vd.render(&mut root, tree(true));
+    click(&root);
+    assert_eq!(*log.borrow(), vec!["capture outer", "button"]);
+}
+
+#[wasm_bindgen_test]
+pub fn hydrate_test() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let container = create_element("div");
+    container.set_inner_html(r#"<p class="greeting">Hello world</p><button>+</button>"#);
+    let paragraph = container.first_child().unwrap();
+    let mut root: Element = container.clone().into();
+    let mut vd = VirtualDom::new();
+    let clicks = Rc::new(Cell::new(0));
+
+    let tree = || {
+        let by_listener = clicks.clone();
+        let by_handler = clicks.clone();
+        fragment(vec![
+            h("p", vec![t("Hello "), t("world")], vec![attr("class", "greeting")]),
+            with_handlers(
+                vec![handler("click", move |_| by_handler.set(by_handler.get() + 10))],
+                with_listeners(
+                    vec![listener("click", move |_| by_listener.set(by_listener.get() + 1))],
+                    h("button", vec![t("+")], vec![]),
+                ),
+            ),
+        ])
+    };
+
+    let mismatches = vd.hydrate(&mut root, tree());
+    assert!(mismatches.is_empty());
+    assert_eq!(container.child_nodes().length(), 2);
+    assert!(container.first_child().unwrap().is_same_node(Some(&paragraph)));
+
+    container
+        .query_selector("button")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<web_sys::HtmlElement>()
+        .unwrap()
+        .click();
+    assert_eq!(clicks.get(), 11);
+}
+
+#[wasm_bindgen_test]
+pub fn hydrate_svg_test() {
+    let container = create_element("div");
+    container.set_inner_html("<svg><foreignObject><p>text</p></foreignObject></svg>");
+    let foreign_object = container.first_child().unwrap().first_child().unwrap();
+    let mut root: Element = container.clone().into();
+    let mut vd = VirtualDom::new();
+
+    let tree = h(
+        "svg",
+        vec![h("foreignObject", vec![h("p", vec![t("text")], vec![])], vec![])],
+        vec![],
+    );
+    assert!(vd.hydrate(&mut root, tree).is_empty());
+    let adopted = container.first_child().unwrap().first_child().unwrap();
+    assert!(adopted.is_same_node(Some(&foreign_object)));
+}
+
+#[wasm_bindgen_test]
+pub fn raw_html_test() {
+    use humus::render::dangerously_set_inner_html;
+
+    let container = create_element("div");
+    let mut root: Element = container.clone().into();
+    let mut vd = VirtualDom::new();
+
+    vd.render(&mut root, dangerously_set_inner_html("pre", "<b>fn</b> main"));
+    assert_eq!(container.inner_html(), "<pre><b>fn</b> main</pre>");
+    let pre = container.first_child().unwrap();
+
+    vd.render(&mut root, dangerously_set_inner_html("pre", "<i>let</i> x"));
+    assert_eq!(container.inner_html(), "<pre><i>let</i> x</pre>");
+    assert!(container.first_child().unwrap().is_same_node(Some(&pre)));
+}
+
+#[wasm_bindgen_test]
+pub fn set_attribute_refuses_event_handlers_test() {
+    let input = create_element("input");
+    let element: Element = input.clone().into();
+
+    assert!(element.set_attribute("onclick", "steal()").is_err());
+    assert!(element.set_attribute("OnFocus", "steal()").is_err());
+    assert!(!input.has_attribute("onclick"));
+    assert!(!input.has_attribute("onfocus"));
+}
+
+#[wasm_bindgen_test]
+pub fn set_attribute_removes_false_boolean_attributes_test() {
+    let input = create_element("input");
+    let element: Element = input.clone().into();
+
+    element.set_attribute("disabled", "").unwrap();
+    assert!(input.has_attribute("disabled"));
+    element.set_attribute("disabled", "false").unwrap();
+    assert!(!input.has_attribute("disabled"));
+    // setting it off when it already is off is fine too
+    element.set_attribute("disabled", "false").unwrap();
+    assert!(!input.has_attribute("disabled"));
+}
+
+struct Counter {
+    count: u32,
+    link: Link<Counter>,
+}
+
+impl Component for Counter {
+    fn render(&self) -> VirtualDomNode {
+        let onclick = self.link.callback(|counter, _| counter.count += 1);
+        html! { <button onclick={onclick}>{self.count.to_string()}</button> }
+    }
+}
+
+#[wasm_bindgen_test]
+pub fn component_test() {
+    let root: Element = create_element("div").into();
+    let app = App::mount(root.clone(), |link| Counter { count: 0, link });
+    assert_eq!(root.clone().inner_html(), "<button>0</button>");
+
+    let button = root.el.as_ref().unwrap().first_element_child().unwrap();
+    let button = button.dyn_into::<web_sys::HtmlElement>().unwrap();
+    button.click();
+    button.click();
+    // both clicks are rendered at once, on the next frame
+    assert_eq!(root.clone().inner_html(), "<button>0</button>");
+    app.flush();
+    assert_eq!(root.clone().inner_html(), "<button>2</button>");
+
+    app.update(|counter| counter.count = 10);
+    assert_eq!(app.with(|counter| counter.count), 10);
+    app.flush();
+    assert_eq!(root.clone().inner_html(), "<button>10</button>");
+}
+
+struct Hooked {
+    name: &'static str,
+    log: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+    child: Option<std::rc::Rc<std::cell::RefCell<Hooked>>>,
+}
+
+impl Hooked {
+    fn record(&self, hook: &str, element: Element) {
+        let tag = element.el.unwrap().tag_name().to_lowercase();
+        self.log.borrow_mut().push(format!("{} {} {}", self.name, hook, tag));
+    }
+}
+
+impl Component for Hooked {
+    fn render(&self) -> VirtualDomNode {
+        match &self.child {
+            Some(child) => h("div", vec![embed(child)], vec![]),
+            None => h("span", vec![], vec![]),
+        }
+    }
+
+    fn mounted(&mut self, element: Element) {
+        self.record("mounted", element);
+    }
+
+    fn updated(&mut self, element: Element) {
+        self.record("updated", element);
+    }
+
+    fn before_unmount(&mut self, element: Element) {
+        self.record("before_unmount", element);
+    }
+}
+
+#[wasm_bindgen_test]
+pub fn lifecycle_test() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let container = create_element("div");
+    let root: Element = container.clone().into();
+    let log = Rc::new(RefCell::new(vec![]));
+    let child = Rc::new(RefCell::new(Hooked {
+        name: "child",
+        log: log.clone(),
+        child: None,
+    }));
+    let app = App::mount(root, |_| Hooked {
+        name: "parent",
+        log: log.clone(),
+        child: Some(child),
+    });
+    assert_eq!(
+        *log.borrow(),
+        vec!["child mounted span", "parent mounted div"]
+    );
+
+    log.borrow_mut().clear();
+    app.update(|_| {});
+    app.flush();
+    assert_eq!(
+        *log.borrow(),
+        vec!["child updated span", "parent updated div"]
+    );
+
+    // another component rendering the same tag is unmounted and mounted, not updated
+    log.borrow_mut().clear();
+    let other = Rc::new(RefCell::new(Hooked {
+        name: "other",
+        log: log.clone(),
+        child: None,
+    }));
+    app.update(|parent| parent.child = Some(other));
+    app.flush();
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            "child before_unmount span",
+            "other mounted span",
+            "parent updated div"
+        ]
+    );
+
+    log.borrow_mut().clear();
+    app.unmount();
+    assert_eq!(
+        *log.borrow(),
+        vec!["parent before_unmount div", "other before_unmount span"]
+    );
+    // nothing is left behind, not even a placeholder
+    assert_eq!(container.child_nodes().length(), 0);
+}
+
+#[wasm_bindgen_test]
+pub fn listener_handle_test() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let button = create_element("button");
+    let mut el: Element = button.clone().into();
+    let clicks = Rc::new(Cell::new(0));
+
+    let counter = clicks.clone();
+    let handle = el.add_event_listener("click", move |_| counter.set(counter.get() + 1));
+    let button: web_sys::HtmlElement = button.dyn_into().unwrap();
+
+    button.click();
+    assert_eq!(clicks.get(), 1);
+
+    drop(handle);
+    button.click();
+    assert_eq!(clicks.get(), 1);
+}
+
+#[wasm_bindgen_test]
+pub fn delegate_test() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let container = create_element("div");
+    container.set_inner_html(
+        "<ul><li class=\"item\" id=\"first\"><b>one</b></li><li id=\"second\">two</li></ul>",
+    );
+    let outside = create_element("li");
+    outside.set_class_name("item");
+    document().body().unwrap().append_child(&outside).unwrap();
+
+    let mut el: Element = container.clone().into();
+    let matched = Rc::new(RefCell::new(vec![]));
+    let seen = matched.clone();
+    let selector = format!("li.{}", "item");
+    let _handle = el.delegate(
+        selector,
+        "click",
+        move |_event, item: Element| seen.borrow_mut().push(item.el.unwrap().id()),
+        false,
+    );
+
+    let click = |el: web_sys::Element| el.dyn_into::<web_sys::HtmlElement>().unwrap().click();
+    // a descendant of the matching element
+    click(container.query_selector("b").unwrap().unwrap());
+    // an element inside the container that doesn't match
+    click(container.query_selector("#second").unwrap().unwrap());
+    // a matching element outside of the container
+    click(outside);
+
+    assert_eq!(*matched.borrow(), vec![String::from("first")]);
+}
+
 /*
 pub fn assert_rendered(container: &web_sys::Element) {
     init_logging();